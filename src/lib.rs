@@ -7,16 +7,64 @@
 //! Build on top of [Reqwest](https://docs.rs/reqwest/latest/reqwest)
 //! 
 
+#[macro_use]
+extern crate serde_derive;
 
-use reqwest::IntoUrl;
+mod url;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{IntoUrl, Method};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
+use sha2::Digest;
 
-#[derive(Debug)]
+use crate::url::UrlBuilder;
+
+/// With the `serde` feature it can be read from and written to configuration files.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct Config {
     pub url: String,
     pub database_name: String,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub username: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub password: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub timeout: Option<Duration>,
+}
+
+/// Fields that are `Some` replace the ones of the base config in [`Config::merge`].
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct ConfigOverride {
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub url: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub database_name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub username: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub password: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub timeout: Option<Duration>,
+}
+
+impl Config {
+    pub fn merge(base: Config, override_: ConfigOverride) -> Config {
+        Config {
+            url: override_.url.unwrap_or(base.url),
+            database_name: override_.database_name.unwrap_or(base.database_name),
+            username: override_.username.or(base.username),
+            password: override_.password.or(base.password),
+            timeout: override_.timeout.or(base.timeout),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -25,6 +73,7 @@ pub enum Error {
     CouchDB(CouchDBError),
     Serde(serde_json::Error),
     Custom(String),
+    Context { message: String, cause: Box<Error> },
 }
 
 impl From<reqwest::Error> for Error {
@@ -51,6 +100,42 @@ impl From<String> for Error {
     }
 }
 
+impl From<&str> for Error {
+    fn from(err: &str) -> Error {
+        Error::Custom(err.to_owned())
+    }
+}
+
+impl Error {
+    pub fn custom(msg: impl std::fmt::Display) -> Error {
+        Error::Custom(msg.to_string())
+    }
+
+    /// Wraps the error with a description of what was being done, keeping it as the `source()`.
+    pub fn context(self, msg: impl std::fmt::Display) -> Error {
+        Error::Context {
+            message: msg.to_string(),
+            cause: Box::new(self),
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::CouchDB(e) => e.code == "not_found",
+            Error::Context { cause, .. } => cause.is_not_found(),
+            _ => false,
+        }
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        match self {
+            Error::CouchDB(e) => e.code == "conflict",
+            Error::Context { cause, .. } => cause.is_conflict(),
+            _ => false,
+        }
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
@@ -58,6 +143,7 @@ impl std::error::Error for Error {
             Error::CouchDB(e) => Some(e),
             Error::Serde(e) => Some(e),
             Error::Custom(_) => None,
+            Error::Context { cause, .. } => Some(cause.as_ref()),
         }
     }
 }
@@ -69,6 +155,42 @@ impl std::fmt::Display for Error {
             Error::CouchDB(e) => write!(f, "{}", e),
             Error::Serde(e) => write!(f, "{}", e),
             Error::Custom(e) => write!(f, "{}", e),
+            Error::Context { message, cause } => write!(f, "{}: {}", message, cause),
+        }
+    }
+}
+
+/// The failure of [`Client::compare_and_swap`], `Conflict` means the revision did not match.
+#[derive(Debug)]
+pub enum SwapError {
+    Conflict,
+    Other(Error),
+}
+
+impl From<Error> for SwapError {
+    fn from(err: Error) -> SwapError {
+        if err.is_conflict() {
+            SwapError::Conflict
+        } else {
+            SwapError::Other(err)
+        }
+    }
+}
+
+impl std::error::Error for SwapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SwapError::Conflict => None,
+            SwapError::Other(e) => Some(e),
+        }
+    }
+}
+
+impl std::fmt::Display for SwapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwapError::Conflict => write!(f, "document revision does not match"),
+            SwapError::Other(e) => write!(f, "{}", e),
         }
     }
 }
@@ -107,6 +229,96 @@ pub trait CouchDBObject {
     fn update_rev(&mut self, rev: String);
 }
 
+/// A raw JSON document that is guaranteed to carry `_id` and `_rev`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CouchDBValue(Value);
+
+impl CouchDBValue {
+    const REQUIRED_FIELDS: [&'static str; 2] = ["_id", "_rev"];
+
+    pub fn new(value: Value) -> Result<Self, Error> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn into_inner(self) -> Value {
+        self.0
+    }
+
+    fn missing_field(&self) -> Option<&'static str> {
+        CouchDBValue::REQUIRED_FIELDS
+            .iter()
+            .find(|key| !self.0.get(**key).is_some_and(Value::is_string))
+            .copied()
+    }
+}
+
+impl std::ops::Deref for CouchDBValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.0
+    }
+}
+
+impl Serialize for CouchDBValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Some(key) = self.missing_field() {
+            return Err(serde::ser::Error::custom(format!(
+                "missing field `{}`",
+                key
+            )));
+        }
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CouchDBValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = CouchDBValue(Value::deserialize(deserializer)?);
+        match value.missing_field() {
+            Some(key) => Err(serde::de::Error::missing_field(key)),
+            None => Ok(value),
+        }
+    }
+}
+
+impl CouchDBObject for CouchDBValue {
+    fn to_id(&self) -> String {
+        self.0["_id"].as_str().unwrap_or_default().to_owned()
+    }
+
+    fn get_rev(&self) -> Option<&str> {
+        self.0.get("_rev").and_then(Value::as_str)
+    }
+
+    fn update_rev(&mut self, rev: String) {
+        if let Value::Object(map) = &mut self.0 {
+            map.insert("_rev".to_owned(), Value::String(rev));
+        }
+    }
+}
+
+pub trait Migration {
+    fn name(&self) -> &str;
+    fn up(&self, client: &Client) -> Result<(), Error>;
+    fn down(&self, client: &Client) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub applied: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MigrationState {
+    #[serde(rename = "_rev")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rev: Option<String>,
+    #[serde(default)]
+    applied: Vec<String>,
+}
+
 fn fetch_value_from_map(map: &serde_json::Map<String, Value>, key: &'static str) -> String {
     map.get(key).unwrap().as_str().unwrap().to_owned()
 }
@@ -129,169 +341,3668 @@ fn to_result<D: DeserializeOwned>(value: Value) -> Result<D, Error> {
     Ok(serde_json::from_value(xd)?)
 }
 
-pub struct Client {
-    client: reqwest::blocking::Client,
-    pub config: Config,
+fn strip_rev(doc: &mut Value) {
+    if let Value::Object(map) = doc {
+        map.remove("_rev");
+    }
 }
 
-impl Client {
-    pub fn new(config: Config) -> Client {
-        Client {
-            client: reqwest::blocking::Client::new(),
-            config,
+fn headers_to_map(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(key, value)| {
+            value
+                .to_str()
+                .ok()
+                .map(|value| (key.as_str().to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// Parses the numeric part of a version like `3.3.2` or `3.3.2-rc.1`.
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let numeric = version
+        .split(|c: char| c != '.' && !c.is_ascii_digit())
+        .next()?;
+    let mut parts = numeric.split('.').map(str::parse::<u32>);
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+/// Collects the dotted field paths of a Mango selector, skipping the `$` operators.
+fn selector_fields(selector: &Value, prefix: &str, fields: &mut Vec<String>) {
+    match selector {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if key.starts_with('$') {
+                    selector_fields(value, prefix, fields);
+                } else {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    let len = fields.len();
+                    selector_fields(value, &path, fields);
+                    if fields.len() == len && !fields.contains(&path) {
+                        fields.push(path);
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                selector_fields(item, prefix, fields);
+            }
         }
+        _ => (),
     }
+}
 
-    pub fn create_db(&self) -> Result<Value, Error> {
-        let res = self.put(&format!(
-            "{}/{}",
-            self.config.url, self.config.database_name
-        ))?;
-        Ok(to_result(res)?)
+/// The numeric part of an update sequence, CouchDB 2+ sends them as `"N-opaque"` strings.
+fn seq_number(seq: &Value) -> Option<u64> {
+    match seq {
+        Value::Number(n) => n.as_u64(),
+        Value::String(text) => text.split('-').next()?.parse().ok(),
+        _ => None,
     }
+}
 
-    pub fn delete_db(&self) -> Result<Value, Error> {
-        let res = self.delete(&format!(
-            "{}/{}",
-            self.config.url, self.config.database_name
-        ))?;
-        Ok(to_result(res)?)
-    }
+/// The field names of an index definition, the fields are either names or `{"name": "asc"}`.
+fn index_def_fields(def: &Value) -> Vec<String> {
+    def["fields"]
+        .as_array()
+        .map(|fields| {
+            fields
+                .iter()
+                .filter_map(|field| match field {
+                    Value::String(name) => Some(name.clone()),
+                    Value::Object(map) => map.keys().next().cloned(),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
-    pub fn put_object<J: Serialize + ?Sized, D: DeserializeOwned>(
-        &self,
-        body: &J,
-    ) -> Result<D, Error> {
-        let res = self.post_json(
-            &format!("{}/{}", self.config.url, self.config.database_name),
-            body,
-        )?;
-        Ok(to_result(res)?)
+/// Turns a failed response into the CouchDB error in its body, or the status when there is none.
+fn error_response(response: reqwest::blocking::Response) -> Error {
+    let status = response.status();
+    match response.json::<Value>().map(to_result::<Value>) {
+        Ok(Err(e)) => e,
+        _ => Error::Custom(format!("status {}", status)),
     }
+}
 
-    pub fn get_latest_revision(&self, id: &str) -> Result<String, Error> {
-        let url = format!("{}/{}/{}", self.config.url, self.config.database_name, id);
+fn header_name(name: &str) -> Result<HeaderName, Error> {
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| Error::Custom(format!("invalid header name {}: {}", name, e)))
+}
 
-        let res = self.head(&url)?;
-        match to_result(res) {
-            Ok(Value::Object(map)) => {
-                let tag_value = map
-                    .get("etag")
-                    .ok_or(Error::Custom("Invalid etag header".to_string()))?;
-                Ok(tag_value.as_str().unwrap().trim_matches('"').to_owned())
-            }
-            Ok(_) => Err(Error::Custom("Invalid etag header".to_string())),
-            Err(e) => Err(e),
+fn content_type(headers: &HeaderMap) -> String {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// The pre 3.x way to read a view without waiting for the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stale {
+    Ok,
+    UpdateAfter,
+    /// The server default, CouchDB rejects `stale=false` so it is never sent.
+    #[default]
+    False,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Update {
+    #[default]
+    True,
+    False,
+    Lazy,
+}
+
+impl From<Stale> for Update {
+    fn from(stale: Stale) -> Self {
+        match stale {
+            Stale::Ok => Update::False,
+            Stale::UpdateAfter => Update::Lazy,
+            Stale::False => Update::True,
         }
     }
+}
 
-    pub fn update_object<J, D>(&self, body: &mut J) -> Result<D, Error>
-    where
-        J: Serialize + ?Sized + CouchDBObject,
-        D: DeserializeOwned,
-    {
-        let url = format!("{}/{}", self.config.url, self.config.database_name);
-        if body.has_rev() {
-            let res = self.post_json(&url, body)?;
-            Ok(to_result(res)?)
-        } else {
-            let id = body.get_id();
-            let rev = self.get_latest_revision(&id)?;
-            body.update_rev(rev);
+impl From<Update> for Stale {
+    fn from(update: Update) -> Self {
+        match update {
+            Update::False => Stale::Ok,
+            Update::Lazy => Stale::UpdateAfter,
+            Update::True => Stale::False,
+        }
+    }
+}
 
-            self.update_object(body)
+/// Decides whether [`ViewQuery`] sends `stale` (`V2`) or `update` (`V3`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CouchDBVersion {
+    V2,
+    #[default]
+    V3,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ViewQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startkey: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endkey: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startkey_docid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endkey_docid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descending: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_docs: Option<bool>,
+    /// Inlines the attachment data as base64 instead of stubs, only used with `include_docs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inclusive_end: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_level: Option<u32>,
+    /// Sent as `update` or `stale` depending on `version`, `update` wins when both are set.
+    #[serde(skip)]
+    pub stale: Option<Stale>,
+    #[serde(skip)]
+    pub update: Option<Update>,
+    #[serde(skip)]
+    pub version: CouchDBVersion,
+}
+
+impl ViewQuery {
+    const JSON_PARAMETERS: [&'static str; 4] = ["key", "keys", "startkey", "endkey"];
+
+    /// All parameters as JSON, with `stale` or `update` picked by `version`.
+    fn to_json(&self) -> Result<serde_json::Map<String, Value>, Error> {
+        let mut map = match serde_json::to_value(self)? {
+            Value::Object(map) => map,
+            _ => return Ok(serde_json::Map::new()),
+        };
+
+        let update = self.update.or_else(|| self.stale.map(Update::from));
+        match (self.version, update) {
+            (_, None) | (CouchDBVersion::V2, Some(Update::True)) => (),
+            (CouchDBVersion::V2, Some(update)) => {
+                map.insert(
+                    "stale".to_owned(),
+                    serde_json::to_value(Stale::from(update))?,
+                );
+            }
+            (CouchDBVersion::V3, Some(update)) => {
+                map.insert("update".to_owned(), serde_json::to_value(update)?);
+            }
         }
+        Ok(map)
     }
 
-    pub fn get_object<D>(&self, id: &str) -> Result<D, Error>
-    where
-        D: DeserializeOwned,
-    {
-        let url = format!("{}/{}/{}", self.config.url, self.config.database_name, id);
-        let res = self.get(&url)?;
-        Ok(to_result(res)?)
+    /// CouchDB expects keys as JSON in the query string, everything else as plain values.
+    fn to_query_pairs(&self) -> Result<Vec<(String, String)>, Error> {
+        Ok(self
+            .to_json()?
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(text) if !ViewQuery::JSON_PARAMETERS.contains(&name.as_str()) => {
+                        text
+                    }
+                    value => value.to_string(),
+                };
+                (name, value)
+            })
+            .collect())
     }
+}
 
-    pub fn delete_object<J, D>(&self, body: &mut J) -> Result<D, Error>
-    where
-        J: Serialize + ?Sized + CouchDBObject,
-        D: DeserializeOwned,
-    {
-        if body.has_rev() {
-            let id = body.get_id();
-            let rev = body.get_rev().unwrap();
+/// Parameters for the `_search` endpoint of Cloudant and CouchDB with the Dreyfus plugin.
+#[cfg(feature = "search")]
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    pub query: String,
+    pub limit: Option<u32>,
+    pub sort: Option<Vec<String>>,
+    pub bookmark: Option<String>,
+    pub include_docs: Option<bool>,
+    pub include_fields: Option<Vec<String>>,
+    pub counts: Option<Vec<String>>,
+    pub ranges: Option<Value>,
+    pub drilldown: Vec<(String, String)>,
+}
 
-            let url = format!(
-                "{}/{}/{}?rev={}",
-                self.config.url, self.config.database_name, id, rev
-            );
+#[cfg(feature = "search")]
+impl SearchQuery {
+    pub fn new(query: impl Into<String>) -> Self {
+        SearchQuery::default().query(query)
+    }
 
-            let res = self.delete(&url)?;
-            Ok(to_result(res)?)
-        } else {
-            let id = body.get_id();
-            let rev = self.get_latest_revision(&id)?;
-            body.update_rev(rev);
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = query.into();
+        self
+    }
 
-            self.delete_object(body)
-        }
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
     }
 
-    pub fn delete_object_by_id<D>(&self, id: &str) -> Result<D, Error>
-    where
-        D: DeserializeOwned,
-    {
-        let rev = self.get_latest_revision(id)?;
-        let url = format!(
-            "{}/{}/{}?rev={}",
-            self.config.url, self.config.database_name, id, rev
-        );
+    pub fn sort(mut self, sort: Vec<String>) -> Self {
+        self.sort = Some(sort);
+        self
+    }
 
-        let res = self.delete(&url)?;
-        Ok(to_result(res)?)
+    pub fn bookmark(mut self, bookmark: impl Into<String>) -> Self {
+        self.bookmark = Some(bookmark.into());
+        self
     }
 
-    // lower level
+    pub fn include_docs(mut self, include_docs: bool) -> Self {
+        self.include_docs = Some(include_docs);
+        self
+    }
 
-    pub fn get<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
-        Ok(self.client.get(url).send()?.json()?)
+    pub fn include_fields(mut self, fields: Vec<String>) -> Self {
+        self.include_fields = Some(fields);
+        self
     }
 
-    pub fn head<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
-        let mut map = serde_json::Map::<String, Value>::new();
+    pub fn counts(mut self, counts: Vec<String>) -> Self {
+        self.counts = Some(counts);
+        self
+    }
 
-        for (key, v) in self.client.head(url).send()?.headers().into_iter() {
-            if let Ok(value) = v.to_str() {
-                map.insert(key.as_str().to_owned(), value.into());
-            }
-        }
+    pub fn ranges(mut self, ranges: Value) -> Self {
+        self.ranges = Some(ranges);
+        self
+    }
 
-        Ok(Value::Object(map))
+    pub fn drilldown(mut self, drilldown: Vec<(String, String)>) -> Self {
+        self.drilldown = drilldown;
+        self
     }
 
-    pub fn put<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
-        Ok(self.client.put(url).send()?.json()?)
+    /// Lists and objects go as JSON, every drilldown pair is its own parameter.
+    fn to_query_pairs(&self) -> Result<Vec<(String, String)>, Error> {
+        let mut pairs = vec![("q".to_owned(), self.query.clone())];
+        if let Some(limit) = self.limit {
+            pairs.push(("limit".to_owned(), limit.to_string()));
+        }
+        if let Some(sort) = &self.sort {
+            pairs.push(("sort".to_owned(), serde_json::to_string(sort)?));
+        }
+        if let Some(bookmark) = &self.bookmark {
+            pairs.push(("bookmark".to_owned(), bookmark.clone()));
+        }
+        if let Some(include_docs) = self.include_docs {
+            pairs.push(("include_docs".to_owned(), include_docs.to_string()));
+        }
+        if let Some(fields) = &self.include_fields {
+            pairs.push(("include_fields".to_owned(), serde_json::to_string(fields)?));
+        }
+        if let Some(counts) = &self.counts {
+            pairs.push(("counts".to_owned(), serde_json::to_string(counts)?));
+        }
+        if let Some(ranges) = &self.ranges {
+            pairs.push(("ranges".to_owned(), ranges.to_string()));
+        }
+        for (field, value) in &self.drilldown {
+            pairs.push((
+                "drilldown".to_owned(),
+                serde_json::to_string(&[field, value])?,
+            ));
+        }
+        Ok(pairs)
     }
+}
 
-    pub fn put_json<U, J>(&self, url: U, json: &J) -> Result<Value, Error>
-    where
-        U: IntoUrl,
-        J: Serialize + ?Sized,
-    {
-        Ok(self.client.put(url).json(json).send()?.json()?)
+#[cfg(feature = "search")]
+#[derive(Debug, Deserialize)]
+pub struct SearchResponse<D> {
+    pub total_rows: u64,
+    #[serde(default)]
+    pub bookmark: String,
+    pub rows: Vec<SearchRow<D>>,
+    #[serde(default)]
+    pub counts: Option<Value>,
+}
+
+#[cfg(feature = "search")]
+#[derive(Debug, Deserialize)]
+pub struct SearchRow<D> {
+    pub id: String,
+    #[serde(default)]
+    pub order: Value,
+    #[serde(default)]
+    pub fields: Value,
+    pub doc: Option<D>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangesFeed {
+    #[default]
+    Normal,
+    Longpoll,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChangesQuery {
+    pub feed: ChangesFeed,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    pub include_docs: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesResponse<D> {
+    pub results: Vec<ChangeItem<D>>,
+    pub last_seq: Value,
+    #[serde(default)]
+    pub pending: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeItem<D> {
+    pub seq: Value,
+    pub id: String,
+    pub changes: Vec<ChangeRevision>,
+    #[serde(default)]
+    pub deleted: bool,
+    pub doc: Option<D>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeRevision {
+    pub rev: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeDiagnostics {
+    pub memory: MemoryStats,
+    pub run_queue: u32,
+    pub context_switches: u64,
+    pub io_input: u64,
+    pub io_output: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MemoryStats {
+    pub total: u64,
+    pub processes: u64,
+    pub atom: u64,
+    pub binary: u64,
+    pub code: u64,
+    pub ets: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CreateDbOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partitioned: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HealthReport {
+    pub server_ok: bool,
+    pub auth_ok: bool,
+    pub db_exists: bool,
+    pub db_readable: bool,
+    pub active_task_count: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectionStatus {
+    pub connected: bool,
+    pub authenticated: bool,
+    pub username: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerInfo {
+    pub couchdb: String,
+    pub version: String,
+    #[serde(default)]
+    pub uuid: Option<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub vendor: Option<Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SizeInfo {
+    pub active: u64,
+    pub external: u64,
+    #[serde(default)]
+    pub file: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatabaseInfo {
+    pub db_name: String,
+    pub doc_count: u64,
+    pub doc_del_count: u64,
+    pub update_seq: Value,
+    #[serde(default)]
+    pub compact_running: bool,
+    #[serde(default)]
+    pub sizes: Option<SizeInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartitionStatistics {
+    pub db_name: String,
+    pub partition: String,
+    pub doc_count: u64,
+    pub doc_del_count: u64,
+    pub sizes: SizeInfo,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AllDocsResponse<D> {
+    pub total_rows: u64,
+    #[serde(default)]
+    pub offset: Option<u64>,
+    pub rows: Vec<AllDocsRow<D>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AllDocsRow<D> {
+    #[serde(default)]
+    pub id: String,
+    pub key: Value,
+    pub value: Option<AllDocsValue>,
+    pub doc: Option<D>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AllDocsValue {
+    pub rev: String,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ViewResponse<K, V, D> {
+    #[serde(default)]
+    pub total_rows: Option<u64>,
+    #[serde(default)]
+    pub offset: Option<u64>,
+    pub rows: Vec<ViewRow<K, V, D>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ViewRow<K, V, D> {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub key: K,
+    pub value: V,
+    pub doc: Option<D>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewDefinition {
+    pub map: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DesignDocument {
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(rename = "_rev")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub views: HashMap<String, ViewDefinition>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+impl CouchDBObject for DesignDocument {
+    fn to_id(&self) -> String {
+        self.id.clone()
     }
 
-    pub fn post_json<U, J>(&self, url: U, json: &J) -> Result<Value, Error>
-    where
-        U: IntoUrl,
-        J: Serialize + ?Sized,
-    {
-        Ok(self.client.post(url).json(json).send()?.json()?)
+    fn get_rev(&self) -> Option<&str> {
+        self.rev.as_deref()
     }
 
-    pub fn delete<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
-        Ok(self.client.delete(url).send()?.json()?)
+    fn update_rev(&mut self, rev: String) {
+        self.rev = Some(rev);
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkGetRequest {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkGetResponse<D> {
+    pub results: Vec<BulkGetResult<D>>,
+}
 
+#[derive(Debug, Deserialize)]
+pub struct BulkGetResult<D> {
+    pub id: String,
+    pub docs: Vec<BulkGetDoc<D>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkGetDoc<D> {
+    Ok(D),
+    Error(BulkGetError),
+}
+
+/// Missing and deleted documents are `NotFound`, any other row error is `Error`.
+#[derive(Debug)]
+pub enum GetBatchResult<D> {
+    Found(D),
+    NotFound,
+    Error(CouchDBError),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulkGetError {
+    pub id: String,
+    pub rev: Option<String>,
+    pub error: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexType {
+    #[default]
+    Json,
+    Text,
+    Special,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexDefinition {
+    pub index: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ddoc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: IndexType,
+}
+
+impl IndexDefinition {
+    pub fn json(fields: &[&str]) -> Self {
+        IndexDefinition {
+            index: serde_json::json!({ "fields": fields }),
+            ddoc: None,
+            name: None,
+            type_: IndexType::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexCreateResponse {
+    pub result: String,
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexInfo {
+    pub ddoc: Option<String>,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: IndexType,
+    pub def: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndexList {
+    pub total_rows: u64,
+    pub indexes: Vec<IndexInfo>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewInfo {
+    pub name: String,
+    pub view_index: ViewIndexInfo,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ViewIndexInfo {
+    pub update_seq: Value,
+    pub updater_running: bool,
+    pub compact_running: bool,
+    pub waiting_clients: u64,
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Only reported by some CouchDB versions.
+    #[serde(default)]
+    pub doc_count: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexStats {
+    pub name: String,
+    pub ddoc: String,
+    pub doc_count: Option<u64>,
+    pub updater_running: Option<bool>,
+}
+
+/// Builds Mango selectors, field operators always end up nested under the field name.
+///
+/// `SelectorBuilder::new().field("age").gt(18).field("age").lt(65).build()` gives
+/// `{"age": {"$gt": 18, "$lt": 65}}`.
+#[derive(Debug, Clone, Default)]
+pub struct SelectorBuilder {
+    selector: serde_json::Map<String, Value>,
+    error: Option<String>,
+}
+
+pub struct FieldSelector {
+    builder: SelectorBuilder,
+    name: String,
+}
+
+impl SelectorBuilder {
+    pub fn new() -> Self {
+        SelectorBuilder::default()
+    }
+
+    pub fn field(self, name: impl Into<String>) -> FieldSelector {
+        FieldSelector {
+            builder: self,
+            name: name.into(),
+        }
+    }
+
+    /// Needs at least two sub-selectors.
+    pub fn and(self, selectors: Vec<SelectorBuilder>) -> Self {
+        self.combine("$and", selectors)
+    }
+
+    /// Needs at least two sub-selectors.
+    pub fn or(self, selectors: Vec<SelectorBuilder>) -> Self {
+        self.combine("$or", selectors)
+    }
+
+    pub fn not(mut self, selector: SelectorBuilder) -> Self {
+        match selector.build() {
+            Ok(value) => {
+                self.selector.insert("$not".to_owned(), value);
+            }
+            Err(e) => self.fail(e.to_string()),
+        }
+        self
+    }
+
+    pub fn build(self) -> Result<Value, Error> {
+        match self.error {
+            Some(message) => Err(Error::Custom(message)),
+            None => Ok(Value::Object(self.selector)),
+        }
+    }
+
+    fn combine(mut self, operator: &str, selectors: Vec<SelectorBuilder>) -> Self {
+        if selectors.len() < 2 {
+            self.fail(format!("{} needs at least two selectors", operator));
+            return self;
+        }
+
+        match selectors.into_iter().map(SelectorBuilder::build).collect() {
+            Ok(values) => {
+                self.selector
+                    .insert(operator.to_owned(), Value::Array(values));
+            }
+            Err(e) => self.fail(e.to_string()),
+        }
+        self
+    }
+
+    fn fail(&mut self, message: String) {
+        self.error.get_or_insert(message);
+    }
+}
+
+impl FieldSelector {
+    pub fn eq(self, value: impl Into<Value>) -> SelectorBuilder {
+        self.operator("$eq", value.into())
+    }
+
+    pub fn gt(self, value: impl Into<Value>) -> SelectorBuilder {
+        self.operator("$gt", value.into())
+    }
+
+    pub fn lt(self, value: impl Into<Value>) -> SelectorBuilder {
+        self.operator("$lt", value.into())
+    }
+
+    pub fn exists(self, exists: bool) -> SelectorBuilder {
+        self.operator("$exists", Value::Bool(exists))
+    }
+
+    fn operator(self, operator: &str, value: Value) -> SelectorBuilder {
+        let mut builder = self.builder;
+        let field = builder
+            .selector
+            .entry(self.name)
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+        if let Value::Object(operators) = field {
+            operators.insert(operator.to_owned(), value);
+        }
+        builder
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortSpec {
+    Asc(String),
+    Desc(String),
+}
+
+impl SortSpec {
+    pub fn asc(field: impl Into<String>) -> SortSpec {
+        SortSpec::Asc(field.into())
+    }
+
+    pub fn desc(field: impl Into<String>) -> SortSpec {
+        SortSpec::Desc(field.into())
+    }
+}
+
+impl Serialize for SortSpec {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            SortSpec::Asc(field) => map.serialize_entry(field, "asc")?,
+            SortSpec::Desc(field) => map.serialize_entry(field, "desc")?,
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FindQuery {
+    pub selector: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Vec<SortSpec>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_index: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bookmark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflicts: Option<bool>,
+}
+
+impl FindQuery {
+    pub fn new(selector: Value) -> Self {
+        FindQuery {
+            selector,
+            limit: None,
+            skip: None,
+            sort: None,
+            fields: None,
+            use_index: None,
+            bookmark: None,
+            conflicts: None,
+        }
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    pub fn sort(mut self, specs: Vec<SortSpec>) -> Self {
+        self.sort = Some(specs);
+        self
+    }
+
+    pub fn fields(mut self, fields: Vec<String>) -> Self {
+        self.fields = Some(fields);
+        self
+    }
+
+    pub fn bookmark(mut self, bookmark: impl Into<String>) -> Self {
+        self.bookmark = Some(bookmark.into());
+        self
+    }
+
+    pub fn conflicts(mut self, conflicts: bool) -> Self {
+        self.conflicts = Some(conflicts);
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FindResponse<D> {
+    pub docs: Vec<D>,
+    #[serde(default)]
+    pub bookmark: Option<String>,
+    #[serde(default)]
+    pub warning: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexRef {
+    /// Empty for the special all-docs index.
+    pub ddoc: String,
+    pub name: String,
+    pub type_: IndexType,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SelectorCoverage {
+    pub covered_fields: Vec<String>,
+    pub uncovered_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    pub index_used: IndexRef,
+    /// The index holds every requested field, so no documents have to be read.
+    pub covering: bool,
+    pub selector_coverage: SelectorCoverage,
+    pub estimated_range_scan: bool,
+    pub mrargs: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexCoverage {
+    /// `ddoc/name` of the chosen index, `None` for the all-docs index.
+    pub index_used: Option<String>,
+    pub full_scan: bool,
+    /// The database document count for full scans, CouchDB gives no estimate for other indexes.
+    pub estimated_docs_examined: Option<u64>,
+    pub selector_fields: Vec<String>,
+    pub fields_covered_by_index: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Members {
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Members {
+    pub fn empty() -> Members {
+        Members::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.roles.is_empty()
+    }
+}
+
+/// A database without a `_security` document returns `{}`, which gives empty members.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SecurityDocument {
+    #[serde(rename = "_rev")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    #[serde(default)]
+    pub admins: Members,
+    #[serde(default)]
+    pub members: Members,
+}
+
+#[derive(Debug)]
+pub struct WatchSummary {
+    pub changes: Vec<ChangeItem<Value>>,
+    pub last_seq: Value,
+    /// The number of changes after `last_seq`, more than zero means it is worth polling again.
+    pub pending: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevisionInfo {
+    pub rev: String,
+    /// The `ETag` header as sent, including the quotes.
+    pub etag: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PutResponse {
+    pub ok: bool,
+    pub id: String,
+    pub rev: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulkResult {
+    pub id: String,
+    #[serde(default)]
+    pub ok: bool,
+    pub rev: Option<String>,
+    pub error: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl BulkResult {
+    pub fn into_result(self) -> Result<PutResponse, Error> {
+        match (self.error, self.rev) {
+            (Some(error), _) => Err(Error::CouchDB(CouchDBError::new(
+                error,
+                self.reason.unwrap_or_default(),
+            ))),
+            (None, Some(rev)) => Ok(PutResponse {
+                ok: true,
+                id: self.id,
+                rev,
+            }),
+            (None, None) => Err(Error::Custom("Invalid bulk result".to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha3_256,
+}
+
+impl HashAlgorithm {
+    fn hex_digest(self, input: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => format!("{:x}", sha2::Sha256::digest(input)),
+            HashAlgorithm::Sha3_256 => format!("{:x}", sha3::Sha3_256::digest(input)),
+        }
+    }
+}
+
+pub enum IdStrategy {
+    ServerGenerated,
+    UuidV4,
+    /// Hashes the JSON body, without `_id` and `_rev`.
+    ContentHash(HashAlgorithm),
+    Custom(Box<dyn Fn(&Value) -> String>),
+}
+
+impl IdStrategy {
+    fn generate(&self, doc: &Value) -> Result<Option<String>, Error> {
+        let id = match self {
+            IdStrategy::ServerGenerated => return Ok(None),
+            IdStrategy::UuidV4 => uuid::Uuid::new_v4().to_simple().to_string(),
+            IdStrategy::ContentHash(algorithm) => {
+                let mut content = doc.clone();
+                if let Value::Object(map) = &mut content {
+                    map.remove("_id");
+                    map.remove("_rev");
+                }
+                algorithm.hex_digest(&serde_json::to_vec(&content)?)
+            }
+            IdStrategy::Custom(f) => f(doc),
+        };
+        Ok(Some(id))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevStatus {
+    Available,
+    Missing,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RevInfo {
+    rev: String,
+    status: RevStatus,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// `(field, old value, new value)`
+    pub changed: Vec<(String, Value, Value)>,
+}
+
+#[derive(Debug)]
+pub struct HistoryEntry<D> {
+    pub rev: String,
+    pub status: RevStatus,
+    pub document: Option<D>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplicationRequest {
+    pub source: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub continuous: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub create_target: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_params: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doc_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicationResponse {
+    pub ok: bool,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub source_last_seq: Option<Value>,
+    #[serde(default)]
+    pub history: Vec<Value>,
+}
+
+/// The fields the replicator adds to a document in `_replicator`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicationDocStatus {
+    #[serde(rename = "_replication_state", default)]
+    pub replication_state: Option<String>,
+    #[serde(rename = "_replication_state_time", default)]
+    pub replication_state_time: Option<String>,
+    #[serde(rename = "_replication_stats", default)]
+    pub replication_stats: Option<ReplicationStats>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReplicationStats {
+    pub revisions_checked: u64,
+    pub missing_revisions_found: u64,
+    pub docs_read: u64,
+    pub docs_written: u64,
+    pub doc_write_failures: u64,
+    pub checkpointed_source_seq: Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct IntegrityReport {
+    pub all_docs_count: u64,
+    pub info_doc_count: u64,
+    pub info_del_count: u64,
+    pub consistent: bool,
+    pub description: Option<String>,
+}
+
+/// The design document name with the outcome of its compaction request.
+pub type CompactionResult = (String, Result<Value, Error>);
+
+#[derive(Debug, Clone, Default)]
+pub struct CopyDatabaseReport {
+    pub total: u64,
+    pub success: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug)]
+pub struct UpdateHandlerResponse {
+    pub body: Value,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct ShowResponse {
+    pub body: String,
+    pub content_type: String,
+    pub status: u16,
+}
+
+#[derive(Debug)]
+pub struct ListResponse {
+    pub body: String,
+    pub content_type: String,
+}
+
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::blocking::Client,
+    request_id_header: Option<HeaderName>,
+    pub config: Config,
+}
+
+pub struct ClientBuilder {
+    config: Config,
+    request_id_header: Option<String>,
+    default_headers: Vec<(String, String)>,
+}
+
+impl ClientBuilder {
+    pub const DEFAULT_REQUEST_ID_HEADER: &'static str = "X-Request-ID";
+
+    pub fn new(config: Config) -> Self {
+        ClientBuilder {
+            config,
+            request_id_header: None,
+            default_headers: Vec::new(),
+        }
+    }
+
+    /// Adds a header that is sent with every request, e.g. a Bearer `Authorization` header.
+    pub fn default_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.default_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sends a fresh UUID v4 under `header_name` with every request, so client logs can be
+    /// matched with the CouchDB logs.
+    pub fn with_request_id_header(mut self, header_name: impl Into<String>) -> Self {
+        self.request_id_header = Some(header_name.into());
+        self
+    }
+
+    pub fn with_request_id(self) -> Self {
+        self.with_request_id_header(ClientBuilder::DEFAULT_REQUEST_ID_HEADER)
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(timeout) = self.config.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.default_headers {
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| Error::Custom(format!("invalid header value for {}: {}", name, e)))?;
+            headers.append(header_name(name)?, value);
+        }
+        builder = builder.default_headers(headers);
+
+        let request_id_header = match self.request_id_header {
+            Some(name) => Some(header_name(&name)?),
+            None => None,
+        };
+
+        Ok(Client {
+            client: builder.build()?,
+            request_id_header,
+            config: self.config,
+        })
+    }
+}
+
+/// Deletes the database when dropped, also when the owning test panics.
+pub struct DatabaseGuard {
+    client: Client,
+}
+
+impl std::ops::Deref for DatabaseGuard {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for DatabaseGuard {
+    fn drop(&mut self) {
+        self.client.delete_db().ok();
+    }
+}
+
+/// Keeps fetched documents in memory, entries never expire and have to be invalidated.
+pub struct DocumentCache<T> {
+    client: Client,
+    entries: HashMap<String, (T, String)>,
+}
+
+impl<T> DocumentCache<T>
+where
+    T: CouchDBObject + DeserializeOwned + Clone,
+{
+    pub fn new(client: Client) -> Self {
+        DocumentCache {
+            client,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn get(&mut self, id: &str) -> Result<T, Error> {
+        if let Some((document, _)) = self.entries.get(id) {
+            return Ok(document.clone());
+        }
+
+        let document: T = self.client.get_object(id)?;
+        let rev = document.get_rev().unwrap_or_default().to_owned();
+        self.entries.insert(id.to_owned(), (document.clone(), rev));
+        Ok(document)
+    }
+
+    pub fn cached_rev(&self, id: &str) -> Option<&str> {
+        self.entries.get(id).map(|(_, rev)| rev.as_str())
+    }
+
+    pub fn invalidate(&mut self, id: &str) {
+        self.entries.remove(id);
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Fetches a view one page per `next()`, stopping after the first short page or error.
+///
+/// Pages continue from the key and id of the last row with `startkey`/`startkey_docid`, so
+/// the server never skips more than one row. Queries with `keys` and reduced views have no
+/// such cursor and fall back to `skip`.
+pub struct ViewPaginator<K, V, D> {
+    client: Client,
+    ddoc: String,
+    view: String,
+    query: ViewQuery,
+    page_size: u32,
+    done: bool,
+    rows: std::marker::PhantomData<ViewRow<K, V, D>>,
+}
+
+impl<K, V, D> ViewPaginator<K, V, D>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    D: DeserializeOwned,
+{
+    fn next_page(&mut self) -> Result<Vec<ViewRow<K, V, D>>, Error> {
+        self.query.limit = Some(u64::from(self.page_size));
+        let rows = self
+            .client
+            .query_view::<Value, Value, Value>(&self.ddoc, &self.view, &self.query)?
+            .rows;
+        if rows.len() < self.page_size as usize {
+            self.done = true;
+        }
+
+        if let Some(last) = rows.last() {
+            match (&last.id, &self.query.keys) {
+                (Some(id), None) => {
+                    self.query.startkey = Some(last.key.clone());
+                    self.query.startkey_docid = Some(id.clone());
+                    self.query.skip = Some(1);
+                }
+                _ => self.query.skip = Some(self.query.skip.unwrap_or(0) + rows.len() as u64),
+            }
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(ViewRow {
+                    id: row.id,
+                    key: serde_json::from_value(row.key)?,
+                    value: serde_json::from_value(row.value)?,
+                    doc: row.doc.map(serde_json::from_value).transpose()?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl<K, V, D> Iterator for ViewPaginator<K, V, D>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    D: DeserializeOwned,
+{
+    type Item = Result<Vec<ViewRow<K, V, D>>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_page() {
+            Ok(rows) if rows.is_empty() => None,
+            Ok(rows) => Some(Ok(rows)),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Client {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+    const CONFLICT_RETRIES: usize = 5;
+    const BULK_DELETE_CHUNK: usize = 1000;
+    const ALL_DOCS_PAGE_SIZE: usize = 1000;
+
+    pub fn new(config: Config) -> Client {
+        ClientBuilder::new(config).build().expect("Client::new()")
+    }
+
+    pub fn builder(config: Config) -> ClientBuilder {
+        ClientBuilder::new(config)
+    }
+
+    fn urls(&self) -> UrlBuilder<'_> {
+        UrlBuilder::new(&self.config)
+    }
+
+    pub fn server_info(&self) -> Result<ServerInfo, Error> {
+        let res = self.get(self.urls().server(&[])?)?;
+        to_result(res)
+    }
+
+    /// Only transport failures are errors, any other answer than the CouchDB welcome is `false`.
+    pub fn is_couchdb(&self) -> Result<bool, Error> {
+        let response = self.request(Method::GET, self.urls().server(&[])?).send()?;
+        if !response.status().is_success() {
+            return Ok(false);
+        }
+
+        let body: Value = response.json().unwrap_or(Value::Null);
+        Ok(body["couchdb"] == "Welcome")
+    }
+
+    /// Checks the configured credentials against `/_session`. An unreachable server gives
+    /// `connected: false` instead of an error.
+    pub fn test_connection(&self) -> Result<ConnectionStatus, Error> {
+        let response = match self
+            .request(Method::GET, self.urls().server(&["_session"])?)
+            .send()
+        {
+            Ok(response) => response,
+            Err(e) if e.is_connect() || e.is_timeout() => return Ok(ConnectionStatus::default()),
+            Err(e) => return Err(e.into()),
+        };
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(ConnectionStatus {
+                connected: true,
+                ..ConnectionStatus::default()
+            });
+        }
+
+        let session: Value = to_result(response.json()?)?;
+        let username = session["userCtx"]["name"].as_str().map(str::to_owned);
+        let roles = serde_json::from_value(session["userCtx"]["roles"].clone()).unwrap_or_default();
+        Ok(ConnectionStatus {
+            connected: true,
+            authenticated: username.is_some(),
+            username,
+            roles,
+        })
+    }
+
+    /// Runs every check even when an earlier one fails, the failures end up in `errors`.
+    ///
+    /// Without configured credentials anonymous access counts as authenticated, a 401 from
+    /// `/_session` never does.
+    pub fn healthcheck(&self) -> Result<HealthReport, Error> {
+        let mut report = HealthReport::default();
+
+        match self.server_info() {
+            Ok(_) => report.server_ok = true,
+            Err(e) => report.errors.push(format!("server: {}", e)),
+        }
+        match self
+            .request(Method::GET, self.urls().server(&["_session"])?)
+            .send()
+        {
+            Ok(response) if response.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                report.errors.push("auth: unauthorized".to_string())
+            }
+            Ok(response) => match response
+                .json()
+                .map_err(Error::from)
+                .and_then(to_result::<Value>)
+            {
+                Ok(session)
+                    if session["userCtx"]["name"].is_string() || self.config.username.is_none() =>
+                {
+                    report.auth_ok = true
+                }
+                Ok(_) => report.errors.push("auth: not authenticated".to_string()),
+                Err(e) => report.errors.push(format!("auth: {}", e)),
+            },
+            Err(e) => report.errors.push(format!("auth: {}", e)),
+        }
+        match self.database_exists() {
+            Ok(true) => report.db_exists = true,
+            Ok(false) => report.errors.push("database: does not exist".to_string()),
+            Err(e) => report.errors.push(format!("database: {}", e)),
+        }
+
+        let query = ViewQuery {
+            limit: Some(1),
+            ..ViewQuery::default()
+        };
+        match self.all_docs::<Value>(&query) {
+            Ok(_) => report.db_readable = true,
+            Err(e) => report.errors.push(format!("read: {}", e)),
+        }
+        match self
+            .get(self.urls().server(&["_active_tasks"])?)
+            .and_then(to_result::<Vec<Value>>)
+        {
+            Ok(tasks) => report.active_task_count = tasks.len(),
+            Err(e) => report.errors.push(format!("active tasks: {}", e)),
+        }
+        Ok(report)
+    }
+
+    pub fn assert_is_couchdb(&self) -> Result<(), Error> {
+        if self.is_couchdb()? {
+            Ok(())
+        } else {
+            Err(Error::Custom(
+                "server does not appear to be CouchDB".to_string(),
+            ))
+        }
+    }
+
+    /// Not called by [`Client::new`], since compatible servers like Cloudant report their own versions.
+    pub fn assert_database_version(&self, min_major: u32, min_minor: u32) -> Result<(), Error> {
+        let (major, minor, patch) = self.get_couch_version()?;
+
+        if (major, minor) < (min_major, min_minor) {
+            return Err(Error::Custom(format!(
+                "requires CouchDB >= {}.{}, found {}.{}.{}",
+                min_major, min_minor, major, minor, patch
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns `(major, minor, patch)`, pre-release tags like `-rc.1` are dropped.
+    pub fn get_couch_version(&self) -> Result<(u32, u32, u32), Error> {
+        let version = self.server_info()?.version;
+        parse_version(&version)
+            .ok_or_else(|| Error::Custom(format!("invalid CouchDB version {}", version)))
+    }
+
+    pub fn create_db(&self) -> Result<Value, Error> {
+        self.create_db_with_options(&CreateDbOptions::default())
+    }
+
+    pub fn create_db_with_options(&self, opts: &CreateDbOptions) -> Result<Value, Error> {
+        let request = self
+            .request(Method::PUT, self.urls().db_root()?)
+            .query(opts);
+        let res = request.send()?.json()?;
+        to_result(res)
+    }
+
+    pub fn database_exists(&self) -> Result<bool, Error> {
+        let url = self.urls().db_root()?;
+        Ok(self
+            .request(Method::HEAD, url)
+            .send()?
+            .status()
+            .is_success())
+    }
+
+    pub fn wait_for_db(&self, timeout: Duration, poll_interval: Duration) -> Result<(), Error> {
+        let start = Instant::now();
+        // the server itself might still be starting, so connection errors are retried as well
+        while !self.database_exists().unwrap_or(false) {
+            if start.elapsed() >= timeout {
+                return Err(Error::Custom("timeout waiting for database".to_string()));
+            }
+            std::thread::sleep(poll_interval);
+        }
+        Ok(())
+    }
+
+    pub fn create_db_guard(&self) -> Result<DatabaseGuard, Error> {
+        self.create_db()?;
+        Ok(DatabaseGuard {
+            client: self.clone(),
+        })
+    }
+
+    pub fn delete_db(&self) -> Result<Value, Error> {
+        let res = self.delete(self.urls().db_root()?)?;
+        to_result(res)
+    }
+
+    pub fn database_info(&self) -> Result<DatabaseInfo, Error> {
+        let res = self.get(self.urls().db_root()?)?;
+        to_result(res)
+    }
+
+    pub fn get_document_count(&self) -> Result<u64, Error> {
+        Ok(self.database_info()?.doc_count)
+    }
+
+    pub fn get_deleted_document_count(&self) -> Result<u64, Error> {
+        Ok(self.database_info()?.doc_del_count)
+    }
+
+    pub fn get_partition_statistics(
+        &self,
+        partition_key: &str,
+    ) -> Result<PartitionStatistics, Error> {
+        let url = self.urls().db(&["_partition", partition_key])?;
+        let res = self.get(url)?;
+        to_result(res)
+    }
+
+    pub fn all_docs_count(&self) -> Result<u64, Error> {
+        let url = self.urls().all_docs()?;
+        let res: Value = to_result(self.get_query(url, &[("limit", 0)])?)?;
+        res["total_rows"]
+            .as_u64()
+            .ok_or(Error::Custom("Invalid total_rows".to_string()))
+    }
+
+    /// Compares the `_all_docs` row count with the database info.
+    ///
+    /// `_all_docs` only lists live documents, so its count is checked against `doc_count`.
+    pub fn verify_database_integrity(&self) -> Result<IntegrityReport, Error> {
+        let all_docs_count = self.all_docs_count()?;
+        let info = self.database_info()?;
+
+        let consistent = all_docs_count == info.doc_count;
+        let description = if consistent {
+            None
+        } else {
+            Some(format!(
+                "_all_docs has {} rows but the database info reports {} documents ({} deleted)",
+                all_docs_count, info.doc_count, info.doc_del_count
+            ))
+        };
+
+        Ok(IntegrityReport {
+            all_docs_count,
+            info_doc_count: info.doc_count,
+            info_del_count: info.doc_del_count,
+            consistent,
+            description,
+        })
+    }
+
+    pub fn all_databases(&self) -> Result<Vec<String>, Error> {
+        let res = self.get(self.urls().server(&["_all_dbs"])?)?;
+        to_result(res)
+    }
+
+    pub fn list_databases_in_range(&self, start: &str, end: &str) -> Result<Vec<String>, Error> {
+        let query = [
+            ("startkey", serde_json::to_string(start)?),
+            ("endkey", serde_json::to_string(end)?),
+        ];
+        let res = self.get_query(self.urls().server(&["_all_dbs"])?, &query)?;
+        to_result(res)
+    }
+
+    pub fn list_databases_matching(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        // database names only contain `a-z0-9_$()+-/`, all of which sort before `{`
+        self.list_databases_in_range(prefix, &format!("{}{{", prefix))
+    }
+
+    pub fn get_node_config_section(
+        &self,
+        node: &str,
+        section: &str,
+    ) -> Result<HashMap<String, String>, Error> {
+        let url = self.urls().server(&["_node", node, "_config", section])?;
+        let res = self.get(url)?;
+        to_result(res)
+    }
+
+    /// Sets the keys one by one and stops at the first failure.
+    pub fn set_node_config_keys(
+        &self,
+        node: &str,
+        section: &str,
+        keys: &HashMap<String, String>,
+    ) -> Result<(), Error> {
+        for (key, value) in keys {
+            let url = self
+                .urls()
+                .server(&["_node", node, "_config", section, key])?;
+            let res = self.put_json(url, value)?;
+            to_result::<Value>(res)?;
+        }
+        Ok(())
+    }
+
+    /// Pass `"_local"` as `node` on single node deployments.
+    pub fn get_node_diagnostics(&self, node: &str) -> Result<NodeDiagnostics, Error> {
+        let res = self.get(self.urls().server(&["_node", node, "_system"])?)?;
+        to_result(res)
+    }
+
+    pub fn replicate(&self, req: &ReplicationRequest) -> Result<ReplicationResponse, Error> {
+        let res = self.post_json(self.urls().server(&["_replicate"])?, req)?;
+        to_result(res)
+    }
+
+    /// Reads the replication document `replication_id` from the `_replicator` database.
+    pub fn get_replication_status(
+        &self,
+        replication_id: &str,
+    ) -> Result<ReplicationDocStatus, Error> {
+        let res = self.get(self.urls().server(&["_replicator", replication_id])?)?;
+        to_result(res)
+    }
+
+    /// Replicates only the documents passing the `filter_name` filter of `filter_ddoc`.
+    pub fn create_filtered_replication(
+        &self,
+        req: &ReplicationRequest,
+        filter_ddoc: &str,
+        filter_name: &str,
+        query_params: Option<Value>,
+    ) -> Result<ReplicationResponse, Error> {
+        let req = ReplicationRequest {
+            filter: Some(format!(
+                "{}/{}",
+                filter_ddoc.trim_start_matches("_design/"),
+                filter_name
+            )),
+            query_params,
+            ..req.clone()
+        };
+        self.replicate(&req)
+    }
+
+    pub fn create_doc_ids_replication(
+        &self,
+        req: &ReplicationRequest,
+        doc_ids: &[&str],
+    ) -> Result<ReplicationResponse, Error> {
+        let req = ReplicationRequest {
+            doc_ids: Some(doc_ids.iter().map(|id| id.to_string()).collect()),
+            ..req.clone()
+        };
+        self.replicate(&req)
+    }
+
+    pub fn put_object<J: Serialize + ?Sized, D: DeserializeOwned>(
+        &self,
+        body: &J,
+    ) -> Result<D, Error> {
+        let res = self.post_json(self.urls().db_root()?, body)?;
+        to_result(res)
+    }
+
+    pub fn put_object_with_id<J: Serialize + ?Sized, D: DeserializeOwned>(
+        &self,
+        id: &str,
+        body: &J,
+    ) -> Result<D, Error> {
+        let res = self.put_json(self.urls().doc(id)?, body)?;
+        to_result(res)
+    }
+
+    /// Creates the document only when `id` is not taken yet, an existing document gives `None`.
+    pub fn put_if_absent<J: Serialize + ?Sized, D: DeserializeOwned>(
+        &self,
+        id: &str,
+        body: &J,
+    ) -> Result<Option<D>, Error> {
+        match self.put_object_with_id(id, body) {
+            Ok(response) => Ok(Some(response)),
+            Err(e) if e.is_conflict() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn put_object_auto_id<J: Serialize + ?Sized, D: DeserializeOwned>(
+        &self,
+        body: &J,
+        strategy: &IdStrategy,
+    ) -> Result<D, Error> {
+        let mut doc = serde_json::to_value(body)?;
+        let id = match strategy.generate(&doc)? {
+            Some(id) => id,
+            None => return self.put_object(&doc),
+        };
+
+        match &mut doc {
+            Value::Object(map) => map.insert("_id".to_owned(), Value::String(id.clone())),
+            _ => return Err(Error::Custom("document is not a JSON object".to_string())),
+        };
+        self.put_object_with_id(&id, &doc)
+    }
+
+    /// PUTs to the `_id` of `doc`, or POSTs when there is none. A `null` `_id` or `_rev`
+    /// counts as missing.
+    pub fn put_document_raw(&self, doc: &Value) -> Result<PutResponse, Error> {
+        let mut doc = doc.clone();
+        let map = doc
+            .as_object_mut()
+            .ok_or(Error::Custom("document is not a JSON object".to_string()))?;
+        for key in &["_id", "_rev"] {
+            if map.get(*key) == Some(&Value::Null) {
+                map.remove(*key);
+            }
+        }
+
+        match map.get("_id") {
+            Some(Value::String(id)) => {
+                let id = id.clone();
+                self.put_object_with_id(&id, &doc)
+            }
+            Some(_) => Err(Error::Custom("_id is not a string".to_string())),
+            None => self.put_object(&doc),
+        }
+    }
+
+    pub fn get_latest_revision(&self, id: &str) -> Result<String, Error> {
+        let etag = self.head_etag(self.urls().doc(id)?)?;
+        Ok(etag.trim_matches('"').to_owned())
+    }
+
+    /// Like [`Client::get_latest_revision`], but a missing or deleted document gives `None`.
+    pub fn get_latest_revision_if_exists(&self, id: &str) -> Result<Option<String>, Error> {
+        let response = self.request(Method::HEAD, self.urls().doc(id)?).send()?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(Error::Custom(format!(
+                "HEAD {} failed with status {}",
+                id, status
+            )));
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(Error::Custom("Invalid etag header".to_string()))?;
+        Ok(Some(etag.trim_matches('"').to_owned()))
+    }
+
+    /// The `Content-Length` of the document as CouchDB would send it, `None` when the server
+    /// leaves the header out.
+    pub fn get_doc_size(&self, id: &str) -> Result<Option<u64>, Error> {
+        let response = self.request(Method::HEAD, self.urls().doc(id)?).send()?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(Error::CouchDB(CouchDBError::new(
+                "not_found".to_string(),
+                "missing".to_string(),
+            )));
+        }
+        if !status.is_success() {
+            return Err(Error::Custom(format!(
+                "HEAD {} failed with status {}",
+                id, status
+            )));
+        }
+
+        Ok(response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()))
+    }
+
+    pub fn get_latest_revision_etag(&self, id: &str) -> Result<RevisionInfo, Error> {
+        let etag = self.head_etag(self.urls().doc(id)?)?;
+        Ok(RevisionInfo {
+            rev: etag.trim_matches('"').to_owned(),
+            etag,
+        })
+    }
+
+    /// Looks up the revisions with one `_all_docs` request, missing and deleted documents
+    /// are left out of the map.
+    pub fn get_latest_revisions_batch(
+        &self,
+        ids: &[&str],
+    ) -> Result<HashMap<String, String>, Error> {
+        let query = ViewQuery {
+            keys: Some(ids.iter().map(|id| Value::from(*id)).collect()),
+            ..ViewQuery::default()
+        };
+        let response: AllDocsResponse<Value> = self.all_docs(&query)?;
+
+        Ok(response
+            .rows
+            .into_iter()
+            .filter_map(|row| match row.value {
+                Some(value) if !value.deleted => Some((row.id, value.rev)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Guesses the partitions from the ids of the first `max_partitions * 10` documents, ids
+    /// without a `:` are skipped. Partitions without documents in that range are missed.
+    pub fn list_partitions(&self, max_partitions: u32) -> Result<Vec<String>, Error> {
+        let query = ViewQuery {
+            limit: Some(u64::from(max_partitions) * 10),
+            include_docs: Some(false),
+            ..ViewQuery::default()
+        };
+        let mut partitions: Vec<String> = Vec::new();
+        for row in self.all_docs::<Value>(&query)?.rows {
+            if let Some((partition, _)) = row.id.split_once(':') {
+                if !partitions.iter().any(|known| known == partition) {
+                    partitions.push(partition.to_owned());
+                }
+            }
+        }
+        Ok(partitions)
+    }
+
+    /// Maps every document id to its current revision, design documents are left out.
+    pub fn all_docs_with_revs(&self) -> Result<HashMap<String, String>, Error> {
+        let query = ViewQuery {
+            include_docs: Some(false),
+            ..ViewQuery::default()
+        };
+        let response: AllDocsResponse<Value> = self.all_docs(&query)?;
+
+        Ok(response
+            .rows
+            .into_iter()
+            .filter(|row| !row.id.starts_with("_design/"))
+            .filter_map(|row| {
+                let id = row.id;
+                row.value.map(|value| (id, value.rev))
+            })
+            .collect())
+    }
+
+    /// The database ETag changes with every write, so comparing two of them tells whether
+    /// anything was written in between.
+    pub fn get_database_etag(&self) -> Result<String, Error> {
+        let etag = self.head_etag(self.urls().db_root()?)?;
+        Ok(etag.trim_matches('"').to_owned())
+    }
+
+    fn head_etag<U: IntoUrl>(&self, url: U) -> Result<String, Error> {
+        let res = self.head(url)?;
+        match to_result(res) {
+            Ok(Value::Object(map)) => {
+                let tag_value = map
+                    .get("etag")
+                    .ok_or(Error::Custom("Invalid etag header".to_string()))?;
+                Ok(tag_value.as_str().unwrap().to_owned())
+            }
+            Ok(_) => Err(Error::Custom("Invalid etag header".to_string())),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn update_object<J, D>(&self, body: &mut J) -> Result<D, Error>
+    where
+        J: Serialize + ?Sized + CouchDBObject,
+        D: DeserializeOwned,
+    {
+        if body.has_rev() {
+            let res = self.post_json(self.urls().db_root()?, body)?;
+            to_result(res)
+        } else {
+            self.refresh_rev(body)?;
+            self.update_object(body)
+        }
+    }
+
+    /// Overwrites the document with `body` at whatever revision it currently has, replacing any
+    /// `_id` and `_rev` in `body`.
+    ///
+    /// Meant for operational repairs only: it silently discards concurrent changes, application
+    /// code should use [`Client::update_object`] and handle conflicts.
+    pub fn force_update<J: Serialize + ?Sized>(
+        &self,
+        id: &str,
+        body: &J,
+    ) -> Result<PutResponse, Error> {
+        let rev = self.get_latest_revision(id)?;
+        let mut doc = serde_json::to_value(body)?;
+        let map = doc
+            .as_object_mut()
+            .ok_or(Error::Custom("document is not a JSON object".to_string()))?;
+        map.insert("_id".to_owned(), Value::from(id));
+        map.insert("_rev".to_owned(), Value::from(rev));
+        self.put_object_with_id(id, &doc)
+    }
+
+    /// Sets the revision of `obj` to the latest revision on the server.
+    pub fn refresh_rev<J: CouchDBObject + ?Sized>(&self, obj: &mut J) -> Result<(), Error> {
+        let rev = self.get_latest_revision(&obj.get_id())?;
+        obj.update_rev(rev);
+        Ok(())
+    }
+
+    /// Saves `body` only when the stored document is still at the revision of `body`, a body
+    /// without revision only succeeds when the document does not exist yet. There is no retry.
+    pub fn compare_and_swap<J>(&self, body: &J) -> Result<PutResponse, SwapError>
+    where
+        J: Serialize + ?Sized + CouchDBObject,
+    {
+        Ok(self.put_object_with_id(&body.get_id(), body)?)
+    }
+
+    pub fn get_object<D>(&self, id: &str) -> Result<D, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let url = self.urls().doc(id)?;
+        let res = self.get(url)?;
+        to_result(res)
+    }
+
+    /// Fetches the document and sets its revision from the `ETag` header, so `D` does not
+    /// need to deserialize `_rev` itself.
+    pub fn get_object_with_rev<D>(&self, id: &str) -> Result<D, Error>
+    where
+        D: DeserializeOwned + CouchDBObject,
+    {
+        let response = self.request(Method::GET, self.urls().doc(id)?).send()?;
+        let rev = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(|etag| etag.trim_matches('"').to_owned());
+
+        let mut object: D = to_result(response.json()?)?;
+        let rev = rev.ok_or(Error::Custom("Invalid etag header".to_string()))?;
+        object.update_rev(rev);
+        Ok(object)
+    }
+
+    /// Reads `_rev` from the raw document before deserializing, so `D` does not need the field.
+    pub fn get_object_rev<D>(&self, id: &str) -> Result<(D, String), Error>
+    where
+        D: DeserializeOwned,
+    {
+        let doc: Value = self.get_object(id)?;
+        let rev = doc["_rev"]
+            .as_str()
+            .ok_or(Error::Custom("document has no _rev".to_string()))?
+            .to_owned();
+        Ok((serde_json::from_value(doc)?, rev))
+    }
+
+    /// Deserializes into `dest` with `Deserialize::deserialize_in_place`, so types that support
+    /// it can reuse their allocations.
+    pub fn get_object_into<D>(&self, id: &str, dest: &mut D) -> Result<(), Error>
+    where
+        D: DeserializeOwned,
+    {
+        let doc: Value = self.get_object(id)?;
+        D::deserialize_in_place(doc, dest)?;
+        Ok(())
+    }
+
+    pub fn get_at_revision<D>(&self, id: &str, rev: &str) -> Result<D, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let url = self.urls().doc_rev(id, rev)?;
+        let res = self.get(url)?;
+        to_result(res)
+    }
+
+    /// Returns `None` when the document is still at `known_rev`.
+    pub fn get_object_if_modified<D>(&self, id: &str, known_rev: &str) -> Result<Option<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::IF_NONE_MATCH,
+            HeaderValue::from_str(&format!("\"{}\"", known_rev))
+                .map_err(|e| Error::Custom(format!("invalid revision {}: {}", known_rev, e)))?,
+        );
+        match self.get_with_headers(self.urls().doc(id)?, headers)? {
+            Some(res) => Ok(Some(to_result(res)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Compares the top level fields of two revisions, `_rev` is left out since it always differs.
+    pub fn diff_documents<D>(
+        &self,
+        id: &str,
+        rev_a: &str,
+        rev_b: &str,
+    ) -> Result<DocumentDiff, Error>
+    where
+        D: DeserializeOwned + Serialize,
+    {
+        let to_map = |rev: &str| -> Result<serde_json::Map<String, Value>, Error> {
+            let doc: D = self.get_at_revision(id, rev)?;
+            match serde_json::to_value(doc)? {
+                Value::Object(mut map) => {
+                    map.remove("_rev");
+                    Ok(map)
+                }
+                _ => Err(Error::Custom(format!("{} is not a JSON object", id))),
+            }
+        };
+        let old = to_map(rev_a)?;
+        let mut new = to_map(rev_b)?;
+
+        let mut diff = DocumentDiff::default();
+        for (field, old_value) in old {
+            match new.remove(&field) {
+                Some(new_value) if new_value != old_value => {
+                    diff.changed.push((field, old_value, new_value))
+                }
+                Some(_) => (),
+                None => diff.removed.push(field),
+            }
+        }
+        diff.added = new.into_iter().map(|(field, _)| field).collect();
+        Ok(diff)
+    }
+
+    pub fn bulk_get<D>(&self, requests: &[BulkGetRequest]) -> Result<BulkGetResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let url = self.urls().bulk_get()?;
+        let res = self.post_json(url, &serde_json::json!({ "docs": requests }))?;
+        to_result(res)
+    }
+
+    /// Returns `(id, document)` pairs so documents of different types can be dispatched on a
+    /// discriminator field. Documents that could not be fetched are left out, use
+    /// [`Client::bulk_get`] to inspect those errors.
+    pub fn bulk_get_dynamic(
+        &self,
+        requests: &[BulkGetRequest],
+    ) -> Result<Vec<(String, Value)>, Error> {
+        let response: BulkGetResponse<Value> = self.bulk_get(requests)?;
+        Ok(response
+            .results
+            .into_iter()
+            .flat_map(|result| {
+                let id = result.id;
+                result.docs.into_iter().filter_map(move |doc| match doc {
+                    BulkGetDoc::Ok(doc) => Some((id.clone(), doc)),
+                    BulkGetDoc::Error(_) => None,
+                })
+            })
+            .collect())
+    }
+
+    /// Fetches all documents with a single `_all_docs` request.
+    ///
+    /// Missing and deleted documents map to an `Err`, the outer error is only returned when
+    /// the request itself fails or a document does not deserialize into `D`.
+    pub fn get_multiple_objects<D>(
+        &self,
+        ids: &[&str],
+    ) -> Result<HashMap<String, Result<D, CouchDBError>>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ViewQuery {
+            keys: Some(ids.iter().map(|id| Value::from(*id)).collect()),
+            include_docs: Some(true),
+            ..ViewQuery::default()
+        };
+        let response: AllDocsResponse<Value> = self.all_docs(&query)?;
+
+        let mut objects = HashMap::new();
+        for row in response.rows {
+            let id = row.key.as_str().unwrap_or(&row.id).to_owned();
+            let object = match (row.doc, row.error) {
+                (Some(doc), _) => Ok(serde_json::from_value(doc)?),
+                (None, Some(error)) => Err(CouchDBError::new(error, "missing".to_string())),
+                (None, None) => Err(CouchDBError::new(
+                    "not_found".to_string(),
+                    "deleted".to_string(),
+                )),
+            };
+            objects.insert(id, object);
+        }
+        Ok(objects)
+    }
+
+    pub fn all_docs_keys<D>(
+        &self,
+        ids: &[&str],
+        include_docs: bool,
+    ) -> Result<AllDocsResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ViewQuery {
+            keys: Some(ids.iter().map(|id| Value::from(*id)).collect()),
+            include_docs: Some(include_docs),
+            ..ViewQuery::default()
+        };
+        self.all_docs(&query)
+    }
+
+    /// Returns the documents in the order of `ids`, with `None` for missing and deleted ones.
+    pub fn get_batch<D>(&self, ids: &[&str]) -> Result<Vec<Option<D>>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let docs: HashMap<String, Value> = self
+            .all_docs_keys::<Value>(ids, true)?
+            .rows
+            .into_iter()
+            .filter_map(|row| match (row.key, row.doc) {
+                (Value::String(key), Some(doc)) => Some((key, doc)),
+                _ => None,
+            })
+            .collect();
+
+        ids.iter()
+            .map(|id| match docs.get(*id) {
+                Some(doc) => Ok(Some(serde_json::from_value(doc.clone())?)),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    pub fn get_batch_with_errors<D>(
+        &self,
+        ids: &[&str],
+    ) -> Result<HashMap<String, GetBatchResult<D>>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let mut objects = HashMap::new();
+        for row in self.all_docs_keys::<Value>(ids, true)?.rows {
+            let id = row.key.as_str().unwrap_or(&row.id).to_owned();
+            let object = match (row.doc, row.error) {
+                (Some(doc), _) => GetBatchResult::Found(serde_json::from_value(doc)?),
+                (None, Some(error)) if error == "not_found" => GetBatchResult::NotFound,
+                (None, Some(error)) => {
+                    GetBatchResult::Error(CouchDBError::new(error, "row error".to_string()))
+                }
+                (None, None) => GetBatchResult::NotFound,
+            };
+            objects.insert(id, object);
+        }
+        Ok(objects)
+    }
+
+    /// Returns the generation of the current revision, the `N` in `N-hash`.
+    pub fn get_revision_count(&self, id: &str) -> Result<u32, Error> {
+        let res: Value = to_result(self.get_query(self.urls().doc(id)?, &[("revs", true)])?)?;
+        res["_revisions"]["start"]
+            .as_u64()
+            .map(|start| start as u32)
+            .ok_or(Error::Custom("Invalid _revisions".to_string()))
+    }
+
+    pub fn bulk_docs<J: Serialize>(&self, docs: &[J]) -> Result<Vec<BulkResult>, Error> {
+        let res = self.post_json(
+            self.urls().bulk_docs()?,
+            &serde_json::json!({ "docs": docs }),
+        )?;
+        to_result(res)
+    }
+
+    /// Fills in the latest revision for documents without one, applies `update_fn` and saves
+    /// everything with one `_bulk_docs` request. Conflicts are not retried, check the results;
+    /// documents that were saved get their new revision.
+    pub fn bulk_update<J, F>(&self, docs: &mut [J], update_fn: F) -> Result<Vec<BulkResult>, Error>
+    where
+        J: Serialize + CouchDBObject + Clone,
+        F: Fn(&mut J),
+    {
+        let ids: Vec<String> = docs
+            .iter()
+            .filter(|doc| !doc.has_rev())
+            .map(|doc| doc.to_id())
+            .collect();
+        if !ids.is_empty() {
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            let mut revs = self.get_latest_revisions_batch(&ids)?;
+            for doc in docs.iter_mut().filter(|doc| !doc.has_rev()) {
+                if let Some(rev) = revs.remove(&doc.to_id()) {
+                    doc.update_rev(rev);
+                }
+            }
+        }
+
+        docs.iter_mut().for_each(&update_fn);
+        let results = self.bulk_docs(docs)?;
+        for (doc, result) in docs.iter_mut().zip(&results) {
+            if let (None, Some(rev)) = (&result.error, &result.rev) {
+                doc.update_rev(rev.clone());
+            }
+        }
+        Ok(results)
+    }
+
+    /// Deletes the given `(id, rev)` pairs with one `_bulk_docs` request.
+    pub fn bulk_delete(&self, docs: &[(&str, &str)]) -> Result<Vec<BulkResult>, Error> {
+        let docs: Vec<Value> = docs
+            .iter()
+            .map(|(id, rev)| serde_json::json!({ "_id": id, "_rev": rev, "_deleted": true }))
+            .collect();
+        self.bulk_docs(&docs)
+    }
+
+    /// Deletes the `losing_revs` of a conflicted document, leaving `winning_rev` as the only
+    /// leaf. Fails when one of the deletes is rejected.
+    pub fn resolve_conflict(
+        &self,
+        id: &str,
+        winning_rev: &str,
+        losing_revs: &[&str],
+    ) -> Result<Vec<PutResponse>, Error> {
+        if losing_revs.contains(&winning_rev) {
+            return Err(Error::Custom(format!(
+                "winning revision {} is also a losing revision",
+                winning_rev
+            )));
+        }
+
+        let docs: Vec<(&str, &str)> = losing_revs.iter().map(|rev| (id, *rev)).collect();
+        self.bulk_delete(&docs)?
+            .into_iter()
+            .map(BulkResult::into_result)
+            .collect()
+    }
+
+    /// Deletes every document but keeps the database with its security and settings, design
+    /// documents are only deleted with `include_design_docs`. Returns the number deleted.
+    pub fn delete_all_documents(&self, include_design_docs: bool) -> Result<u64, Error> {
+        let mut revs: Vec<(String, String)> = self.all_docs_with_revs()?.into_iter().collect();
+        if include_design_docs {
+            for row in self.all_docs_design::<Value>(false)?.rows {
+                if let Some(value) = row.value {
+                    revs.push((row.id, value.rev));
+                }
+            }
+        }
+
+        let mut deleted = 0;
+        for chunk in revs.chunks(Client::BULK_DELETE_CHUNK) {
+            let docs: Vec<(&str, &str)> = chunk
+                .iter()
+                .map(|(id, rev)| (id.as_str(), rev.as_str()))
+                .collect();
+            deleted += self
+                .bulk_delete(&docs)?
+                .iter()
+                .filter(|result| result.error.is_none())
+                .count() as u64;
+        }
+        Ok(deleted)
+    }
+
+    /// Like [`Client::bulk_docs`], but drops any `_rev` first so every document is a create.
+    pub fn bulk_create<J: Serialize>(&self, docs: &[J]) -> Result<Vec<BulkResult>, Error> {
+        let docs = docs
+            .iter()
+            .map(|doc| {
+                let mut doc = serde_json::to_value(doc)?;
+                strip_rev(&mut doc);
+                Ok(doc)
+            })
+            .collect::<Result<Vec<Value>, Error>>()?;
+        self.bulk_docs(&docs)
+    }
+
+    /// Returns the known revisions, newest first, with the document filled in for every
+    /// revision that is still available. `limit` caps the number of revisions looked at.
+    pub fn get_document_history<D>(
+        &self,
+        id: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<HistoryEntry<D>>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let res: Value = to_result(self.get_query(self.urls().doc(id)?, &[("revs_info", true)])?)?;
+        let mut revs_info: Vec<RevInfo> = serde_json::from_value(res["_revs_info"].clone())?;
+        if let Some(limit) = limit {
+            revs_info.truncate(limit as usize);
+        }
+
+        revs_info
+            .into_iter()
+            .map(|info| {
+                let document = match info.status {
+                    RevStatus::Available => Some(self.get_at_revision(id, &info.rev)?),
+                    RevStatus::Missing | RevStatus::Deleted => None,
+                };
+                Ok(HistoryEntry {
+                    rev: info.rev,
+                    status: info.status,
+                    document,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the document exactly as CouchDB sends it, without checking for error objects.
+    pub fn get_raw_document(&self, id: &str) -> Result<Value, Error> {
+        let url = self.urls().doc(id)?;
+        self.get(url)
+    }
+
+    /// Copies the attachment into `writer` without holding it in memory, returns the number
+    /// of bytes written.
+    pub fn get_attachment_stream<W: std::io::Write>(
+        &self,
+        doc_id: &str,
+        name: &str,
+        writer: &mut W,
+    ) -> Result<u64, Error> {
+        let mut response = self
+            .request(Method::GET, self.urls().attachment(doc_id, name)?)
+            .send()?;
+        if !response.status().is_success() {
+            return Err(error_response(response));
+        }
+        Ok(response.copy_to(writer)?)
+    }
+
+    /// Downloads bytes `start` to `end`, both inclusive, with a `Range` request.
+    pub fn get_attachment_range(
+        &self,
+        doc_id: &str,
+        name: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let response = self
+            .request(Method::GET, self.urls().attachment(doc_id, name)?)
+            .header(reqwest::header::RANGE, format!("bytes={}-{}", start, end))
+            .send()?;
+        if !response.status().is_success() {
+            return Err(error_response(response));
+        }
+        Ok(response.bytes()?.to_vec())
+    }
+
+    /// Uploads `content_length` bytes from `reader` as the body of the request. The reader is
+    /// taken by value because the blocking client reads it while sending.
+    pub fn put_attachment_stream<R>(
+        &self,
+        doc_id: &str,
+        rev: &str,
+        name: &str,
+        content_type: &str,
+        reader: R,
+        content_length: u64,
+    ) -> Result<PutResponse, Error>
+    where
+        R: std::io::Read + Send + 'static,
+    {
+        let mut url = self.urls().attachment(doc_id, name)?;
+        url.query_pairs_mut().append_pair("rev", rev);
+        let res = self
+            .request(Method::PUT, url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(reqwest::blocking::Body::sized(reader, content_length))
+            .send()?
+            .json()?;
+        to_result(res)
+    }
+
+    pub fn delete_object<J, D>(&self, body: &mut J) -> Result<D, Error>
+    where
+        J: Serialize + ?Sized + CouchDBObject,
+        D: DeserializeOwned,
+    {
+        if body.has_rev() {
+            let id = body.get_id();
+            let rev = body.get_rev().unwrap();
+
+            let url = self.urls().doc_rev(&id, rev)?;
+
+            let res = self.delete(url)?;
+            to_result(res)
+        } else {
+            self.refresh_rev(body)?;
+            self.delete_object(body)
+        }
+    }
+
+    pub fn delete_object_by_id<D>(&self, id: &str) -> Result<D, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let rev = self.get_latest_revision(id)?;
+        let url = self.urls().doc_rev(id, &rev)?;
+
+        let res = self.delete(url)?;
+        to_result(res)
+    }
+
+    /// Returns `false` when the document was already gone, also when someone else deleted it
+    /// between looking up the revision and the delete.
+    pub fn delete_object_safe(&self, id: &str) -> Result<bool, Error> {
+        let rev = match self.get_latest_revision_if_exists(id)? {
+            Some(rev) => rev,
+            None => return Ok(false),
+        };
+        let res = self.delete(self.urls().doc_rev(id, &rev)?)?;
+        match to_result::<PutResponse>(res) {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn changes<D>(&self, query: &ChangesQuery) -> Result<ChangesResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let url = self.urls().changes()?;
+        let res = self.get_query(url, query)?;
+        to_result(res)
+    }
+
+    /// The changes of one document through the built-in `_doc_ids` filter, with the documents
+    /// included. CouchDB only keeps the latest change per document, so this is at most one entry.
+    pub fn get_changes_for_doc<D>(
+        &self,
+        doc_id: &str,
+        since: Option<&str>,
+    ) -> Result<ChangesResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ChangesQuery {
+            since: since.map(str::to_owned),
+            include_docs: true,
+            ..ChangesQuery::default()
+        };
+        let doc_ids = serde_json::to_string(&[doc_id])?;
+        let res = self
+            .request(Method::GET, self.urls().changes()?)
+            .query(&query)
+            .query(&[("filter", "_doc_ids"), ("doc_ids", doc_ids.as_str())])
+            .send()?
+            .json()?;
+        to_result(res)
+    }
+
+    /// `since` accepts a sequence from an earlier response as well as `"0"` and `"now"`.
+    pub fn get_changes_since<D>(
+        &self,
+        since: impl Into<String>,
+        include_docs: bool,
+    ) -> Result<ChangesResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ChangesQuery {
+            feed: ChangesFeed::Normal,
+            since: Some(since.into()),
+            include_docs,
+            ..ChangesQuery::default()
+        };
+        self.changes(&query)
+    }
+
+    pub fn watch_database(&self, since: &str, limit: u64) -> Result<WatchSummary, Error> {
+        let query = ChangesQuery {
+            feed: ChangesFeed::Normal,
+            since: Some(since.to_owned()),
+            limit: Some(limit),
+            ..ChangesQuery::default()
+        };
+        let response = self.changes::<Value>(&query)?;
+
+        Ok(WatchSummary {
+            changes: response.results,
+            last_seq: response.last_seq,
+            pending: response.pending.unwrap_or(0),
+        })
+    }
+
+    /// Returns the current update sequence, a good `since` for only processing new changes.
+    pub fn get_current_seq(&self) -> Result<Value, Error> {
+        let query = ChangesQuery {
+            since: Some("now".to_string()),
+            limit: Some(0),
+            ..ChangesQuery::default()
+        };
+        Ok(self.changes::<Value>(&query)?.last_seq)
+    }
+
+    /// The number of changes after `since`, servers older than CouchDB 2.1 do not report it.
+    pub fn get_pending_changes_count(&self, since: &str) -> Result<u64, Error> {
+        let query = ChangesQuery {
+            since: Some(since.to_owned()),
+            limit: Some(0),
+            ..ChangesQuery::default()
+        };
+        self.changes::<Value>(&query)?
+            .pending
+            .ok_or(Error::Custom("pending not supported".to_string()))
+    }
+
+    /// Update handlers may answer with plain text, which is returned as a `Value::String`.
+    pub fn post_to_update_handler<J: Serialize + ?Sized>(
+        &self,
+        ddoc: &str,
+        function: &str,
+        doc_id: &str,
+        body: &J,
+    ) -> Result<UpdateHandlerResponse, Error> {
+        let url = self
+            .urls()
+            .design_path(ddoc, &["_update", function, doc_id])?;
+
+        let response = self.request(Method::POST, url).json(body).send()?;
+        let headers = headers_to_map(response.headers());
+        let text = response.text()?;
+        let body = serde_json::from_str(&text).unwrap_or(Value::String(text));
+
+        Ok(UpdateHandlerResponse {
+            body: to_result(body)?,
+            headers,
+        })
+    }
+
+    pub fn show(&self, ddoc: &str, function: &str, doc_id: &str) -> Result<ShowResponse, Error> {
+        let url = self
+            .urls()
+            .design_path(ddoc, &["_show", function, doc_id])?;
+
+        let response = self.request(Method::GET, url).send()?;
+        let status = response.status().as_u16();
+        let content_type = content_type(response.headers());
+
+        Ok(ShowResponse {
+            body: response.text()?,
+            content_type,
+            status,
+        })
+    }
+
+    /// `view` may be `other_ddoc/view` to run the list function over a view of another design document.
+    pub fn query_list(
+        &self,
+        ddoc: &str,
+        list_fn: &str,
+        view: &str,
+        query: &ViewQuery,
+    ) -> Result<ListResponse, Error> {
+        let mut path = vec!["_list", list_fn];
+        path.extend(view.split('/'));
+        let url = self.urls().design_path(ddoc, &path)?;
+
+        let response = self.view_request(url, query)?.send()?;
+        let content_type = content_type(response.headers());
+
+        Ok(ListResponse {
+            body: response.text()?,
+            content_type,
+        })
+    }
+
+    pub fn delete_design_doc(&self, name: &str, rev: &str) -> Result<PutResponse, Error> {
+        let mut url = self.urls().design(name)?;
+        url.query_pairs_mut().append_pair("rev", rev);
+
+        let res = self.delete(url)?;
+        to_result(res)
+    }
+
+    /// Builds the views of `new_doc` under `_design/{name}-new` before saving it under its own
+    /// name. Both documents get the same view signature, so the index built for the temporary
+    /// document is reused and queries keep using the old index until the swap.
+    ///
+    /// With `wait_for_index` this blocks until the index has caught up, for at most the given
+    /// duration. On a timeout the temporary document is left in place and keeps building.
+    pub fn apply_design_doc_update(
+        &self,
+        new_doc: &DesignDocument,
+        wait_for_index: Option<Duration>,
+    ) -> Result<(), Error> {
+        let name = new_doc.id.trim_start_matches("_design/");
+        let temp_name = format!("{}-new", name);
+        let temp_id = format!("_design/{}", temp_name);
+
+        let temp = DesignDocument {
+            id: temp_id.clone(),
+            rev: self.get_latest_revision_if_exists(&temp_id)?,
+            ..new_doc.clone()
+        };
+        let temp_rev = self
+            .put_object_with_id::<_, PutResponse>(&temp_id, &temp)?
+            .rev;
+
+        if let (Some(timeout), Some(view)) = (wait_for_index, new_doc.views.keys().next()) {
+            let target = self.get_current_seq()?;
+            let query = ViewQuery {
+                limit: Some(0),
+                reduce: Some(false),
+                update: Some(Update::Lazy),
+                ..ViewQuery::default()
+            };
+            self.query_view::<Value, Value, Value>(&temp_name, view, &query)?;
+            self.wait_for_index_seq(&temp_name, &target, timeout)?;
+        }
+
+        let id = format!("_design/{}", name);
+        let canonical = DesignDocument {
+            id: id.clone(),
+            rev: self.get_latest_revision_if_exists(&id)?,
+            ..new_doc.clone()
+        };
+        self.put_object_with_id::<_, PutResponse>(&id, &canonical)?;
+        self.delete_design_doc(&temp_name, &temp_rev)?;
+        Ok(())
+    }
+
+    /// Deletes the design document at whatever revision it currently has.
+    ///
+    /// The revision is passed on verbatim, so never replicated `0-` revisions work as well.
+    pub fn force_delete_design_doc(&self, name: &str) -> Result<PutResponse, Error> {
+        let rev = self.get_latest_revision(&format!("_design/{}", name))?;
+        self.delete_design_doc(name, &rev)
+    }
+
+    pub fn query_view<K, V, D>(
+        &self,
+        ddoc: &str,
+        view: &str,
+        query: &ViewQuery,
+    ) -> Result<ViewResponse<K, V, D>, Error>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+        D: DeserializeOwned,
+    {
+        let url = self.urls().view(ddoc, view)?;
+        let res = self.view_request(url, query)?.send()?.json()?;
+        to_result(res)
+    }
+
+    pub fn query_view_paginated<K, V, D>(
+        &self,
+        ddoc: &str,
+        view: &str,
+        query: ViewQuery,
+        page_size: u32,
+    ) -> ViewPaginator<K, V, D> {
+        ViewPaginator {
+            client: self.clone(),
+            ddoc: ddoc.to_owned(),
+            view: view.to_owned(),
+            query,
+            page_size: page_size.max(1),
+            done: false,
+            rows: std::marker::PhantomData,
+        }
+    }
+
+    /// Reads `total_rows` with `limit=0` and `reduce=false`, so no rows are transferred.
+    ///
+    /// CouchDB reports `total_rows` for the whole view, key ranges in `query` do not narrow
+    /// it down; count a range with a `_count` reduce instead.
+    pub fn get_view_count(
+        &self,
+        ddoc: &str,
+        view: &str,
+        query: Option<&ViewQuery>,
+    ) -> Result<u64, Error> {
+        let query = ViewQuery {
+            limit: Some(0),
+            reduce: Some(false),
+            include_docs: None,
+            ..query.cloned().unwrap_or_default()
+        };
+        let response: ViewResponse<Value, Value, Value> = self.query_view(ddoc, view, &query)?;
+        response
+            .total_rows
+            .ok_or(Error::Custom("view response has no total_rows".to_string()))
+    }
+
+    #[cfg(feature = "search")]
+    pub fn search<D>(
+        &self,
+        ddoc: &str,
+        index: &str,
+        query: &SearchQuery,
+    ) -> Result<SearchResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let url = self.urls().design_path(ddoc, &["_search", index])?;
+        let res = self.get_query(url, &query.to_query_pairs()?)?;
+        to_result(res)
+    }
+
+    /// Same as [`Client::query_view`], but sends every parameter in the JSON body so nothing
+    /// ends up in the URL.
+    pub fn post_view<K, V, D>(
+        &self,
+        ddoc: &str,
+        view: &str,
+        query: &ViewQuery,
+    ) -> Result<ViewResponse<K, V, D>, Error>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+        D: DeserializeOwned,
+    {
+        let res = self.post_json(self.urls().view(ddoc, view)?, &query.to_json()?)?;
+        to_result(res)
+    }
+
+    /// Adds or replaces one view in `ddoc`, creating the design document when it does not exist
+    /// yet. Concurrent updates of the design document are retried.
+    pub fn create_view(
+        &self,
+        ddoc: &str,
+        view_name: &str,
+        map_fn: &str,
+        reduce_fn: Option<&str>,
+    ) -> Result<PutResponse, Error> {
+        let id = format!("_design/{}", ddoc.trim_start_matches("_design/"));
+        let mut attempt = 0;
+        loop {
+            let mut design = match self.get_object::<DesignDocument>(&id) {
+                Ok(design) => design,
+                Err(e) if e.is_not_found() => DesignDocument {
+                    id: id.clone(),
+                    ..DesignDocument::default()
+                },
+                Err(e) => return Err(e),
+            };
+            design.views.insert(
+                view_name.to_owned(),
+                ViewDefinition {
+                    map: map_fn.to_owned(),
+                    reduce: reduce_fn.map(str::to_owned),
+                },
+            );
+
+            match self.put_object_with_id(&id, &design) {
+                Err(e) if e.is_conflict() && attempt < Client::CONFLICT_RETRIES => attempt += 1,
+                result => return result,
+            }
+        }
+    }
+
+    /// Counts the documents with conflicts through the `conflicts` view of the
+    /// `_design/rustbank_conflicts` design document, which is created when missing.
+    pub fn get_conflicts_count(&self) -> Result<u64, Error> {
+        let mut views = HashMap::new();
+        views.insert(
+            "conflicts".to_string(),
+            ViewDefinition {
+                map: "function(doc) { if (doc._conflicts) { emit(doc._id, null); } }".to_string(),
+                reduce: Some("_count".to_string()),
+            },
+        );
+        let ddoc = DesignDocument {
+            id: "_design/rustbank_conflicts".to_string(),
+            views,
+            ..DesignDocument::default()
+        };
+        match self.put_object_with_id::<_, PutResponse>(&ddoc.id, &ddoc) {
+            Err(e) if !e.is_conflict() => return Err(e),
+            _ => (),
+        }
+
+        let query = ViewQuery {
+            reduce: Some(true),
+            ..ViewQuery::default()
+        };
+        let response: ViewResponse<Value, u64, Value> =
+            self.query_view("rustbank_conflicts", "conflicts", &query)?;
+        Ok(response.rows.first().map_or(0, |row| row.value))
+    }
+
+    pub fn get_view_stale<K, V, D>(
+        &self,
+        ddoc: &str,
+        view: &str,
+        stale: Stale,
+        query: &ViewQuery,
+    ) -> Result<ViewResponse<K, V, D>, Error>
+    where
+        K: DeserializeOwned,
+        V: DeserializeOwned,
+        D: DeserializeOwned,
+    {
+        let query = ViewQuery {
+            stale: Some(stale),
+            update: None,
+            ..query.clone()
+        };
+        self.query_view(ddoc, view, &query)
+    }
+
+    pub fn get_view_info(&self, ddoc: &str) -> Result<ViewInfo, Error> {
+        let res = self.get(self.urls().design_path(ddoc, &["_info"])?)?;
+        to_result(res)
+    }
+
+    /// Looks up the view index info of every index with a design document, the statistics are
+    /// `None` when the design document has disappeared in the meantime.
+    pub fn get_index_stats(&self) -> Result<Vec<IndexStats>, Error> {
+        let mut stats = Vec::new();
+        for index in self.list_indexes()?.indexes {
+            let ddoc = match index.ddoc {
+                Some(ddoc) => ddoc,
+                None => continue,
+            };
+            let view_index = match self.get_view_info(ddoc.trim_start_matches("_design/")) {
+                Ok(info) => Some(info.view_index),
+                Err(e) if e.is_not_found() => None,
+                Err(e) => return Err(e),
+            };
+            stats.push(IndexStats {
+                name: index.name,
+                ddoc,
+                doc_count: view_index.as_ref().and_then(|info| info.doc_count),
+                updater_running: view_index.map(|info| info.updater_running),
+            });
+        }
+        Ok(stats)
+    }
+
+    /// Polls the design document info until its view index is no longer being updated.
+    pub fn wait_for_view_index_ready(&self, ddoc: &str, timeout: Duration) -> Result<(), Error> {
+        let start = Instant::now();
+        while self.get_view_info(ddoc)?.view_index.updater_running {
+            if start.elapsed() >= timeout {
+                return Err(Error::Custom("timeout waiting for view index".to_string()));
+            }
+            std::thread::sleep(Client::POLL_INTERVAL);
+        }
+        Ok(())
+    }
+
+    /// Waits until the view index behind a Mango index has caught up with the database
+    /// sequence at the time of the call. The build is started with an `update=lazy` query on
+    /// the view the index is stored as.
+    pub fn await_index(
+        &self,
+        ddoc: &str,
+        index_name: &str,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        if self.get_index_info(ddoc, index_name)?.is_none() {
+            return Err(Error::Custom(format!("index {} not found", index_name)));
+        }
+        let ddoc = ddoc.trim_start_matches("_design/");
+        let target = self.get_current_seq()?;
+        let query = ViewQuery {
+            limit: Some(0),
+            reduce: Some(false),
+            update: Some(Update::Lazy),
+            ..ViewQuery::default()
+        };
+        self.query_view::<Value, Value, Value>(ddoc, index_name, &query)?;
+        self.wait_for_index_seq(ddoc, &target, timeout)
+    }
+
+    /// Polls until the view index of `ddoc` is idle and has processed `target`. Checking the
+    /// sequence as well catches an index build that has not started yet.
+    fn wait_for_index_seq(
+        &self,
+        ddoc: &str,
+        target: &Value,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let target = seq_number(target).unwrap_or(0);
+        let start = Instant::now();
+        loop {
+            let index = self.get_view_info(ddoc)?.view_index;
+            let caught_up = seq_number(&index.update_seq).is_some_and(|seq| seq >= target);
+            if !index.updater_running && caught_up {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                return Err(Error::Custom("timeout waiting for index".to_string()));
+            }
+            std::thread::sleep(Client::POLL_INTERVAL);
+        }
+    }
+
+    pub fn all_docs<D>(&self, query: &ViewQuery) -> Result<AllDocsResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let url = self.urls().all_docs()?;
+        let res = self.view_request(url, query)?.send()?.json()?;
+        to_result(res)
+    }
+
+    /// Lists only the design documents, use [`DesignDocument`] for `D` when including the docs.
+    ///
+    /// `"_design0"` bounds the range because `0` sorts right after `/`.
+    pub fn all_docs_design<D>(&self, include_docs: bool) -> Result<AllDocsResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ViewQuery {
+            startkey: Some(Value::from("_design/")),
+            endkey: Some(Value::from("_design0")),
+            include_docs: Some(include_docs),
+            ..ViewQuery::default()
+        };
+        self.all_docs(&query)
+    }
+
+    /// Every document, design documents included, for exports and backups.
+    pub fn get_all_docs_full<D>(&self, include_docs: bool) -> Result<AllDocsResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ViewQuery {
+            include_docs: Some(include_docs),
+            ..ViewQuery::default()
+        };
+        self.all_docs(&query)
+    }
+
+    /// Names of the design documents, without the `_design/` prefix.
+    pub fn list_all_design_docs(&self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .all_docs_design::<Value>(false)?
+            .rows
+            .into_iter()
+            .map(|row| row.id.trim_start_matches("_design/").to_owned())
+            .collect())
+    }
+
+    /// `ddoc` may be given with or without the `_design/` prefix.
+    pub fn compact_view(&self, ddoc: &str) -> Result<Value, Error> {
+        let ddoc = ddoc.trim_start_matches("_design/");
+        let res = self.post_json(self.urls().db(&["_compact", ddoc])?, &serde_json::json!({}))?;
+        to_result(res)
+    }
+
+    /// Starts view compaction for every design document, a failure for one design document
+    /// does not stop the others.
+    pub fn compact_all_views(&self) -> Result<Vec<CompactionResult>, Error> {
+        Ok(self
+            .list_all_design_docs()?
+            .into_iter()
+            .map(|ddoc| {
+                let result = self.compact_view(&ddoc);
+                (ddoc, result)
+            })
+            .collect())
+    }
+
+    pub fn find_one<D>(&self, selector: Value) -> Result<Option<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = FindQuery::new(selector).limit(1);
+        Ok(self.find(&query)?.docs.into_iter().next())
+    }
+
+    pub fn find_one_required<D>(&self, selector: Value) -> Result<D, Error>
+    where
+        D: DeserializeOwned,
+    {
+        self.find_one(selector)?
+            .ok_or(Error::Custom("not found".to_string()))
+    }
+
+    pub fn find_and_update<D, F>(
+        &self,
+        query: FindQuery,
+        update_fn: F,
+    ) -> Result<Vec<Result<PutResponse, Error>>, Error>
+    where
+        D: DeserializeOwned + Serialize + CouchDBObject,
+        F: Fn(&mut D),
+    {
+        let mut docs = self.find::<D>(&query)?.docs;
+        docs.iter_mut().for_each(update_fn);
+
+        Ok(self
+            .bulk_docs(&docs)?
+            .into_iter()
+            .map(BulkResult::into_result)
+            .collect())
+    }
+
+    pub fn get_all_conflicts<D>(&self, limit: Option<u64>) -> Result<Vec<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let mut query = FindQuery::new(serde_json::json!({ "_conflicts": { "$exists": true } }))
+            .conflicts(true);
+        query.limit = limit;
+        Ok(self.find(&query)?.docs)
+    }
+
+    pub fn get_security(&self) -> Result<SecurityDocument, Error> {
+        let res = self.get(self.urls().db(&["_security"])?)?;
+        to_result(res)
+    }
+
+    pub fn set_security(&self, security: &SecurityDocument) -> Result<(), Error> {
+        let res = self.put_json(self.urls().db(&["_security"])?, security)?;
+        to_result::<Value>(res)?;
+        Ok(())
+    }
+
+    /// A database without members can be read by anyone.
+    pub fn database_is_public(&self) -> Result<bool, Error> {
+        Ok(self.get_security()?.members.is_empty())
+    }
+
+    pub fn add_admin_role(&self, role: &str) -> Result<(), Error> {
+        self.update_security_fn(|security| {
+            if !security.admins.roles.iter().any(|r| r == role) {
+                security.admins.roles.push(role.to_owned());
+            }
+        })
+    }
+
+    pub fn remove_member_role(&self, role: &str) -> Result<(), Error> {
+        self.update_security_fn(|security| security.members.roles.retain(|r| r != role))
+    }
+
+    /// Reads the security document, applies `f` and writes it back, retrying on conflicts.
+    ///
+    /// Servers that return a `_rev` for `_security` get it sent back with the update.
+    pub fn update_security_fn<F: Fn(&mut SecurityDocument)>(&self, f: F) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            let mut security = self.get_security()?;
+            f(&mut security);
+            match self.set_security(&security) {
+                Err(e) if e.is_conflict() && attempt < Client::CONFLICT_RETRIES => attempt += 1,
+                result => return result,
+            }
+        }
+    }
+
+    pub fn find<D>(&self, query: &FindQuery) -> Result<FindResponse<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let res = self.post_json(self.urls().find()?, query)?;
+        to_result(res)
+    }
+
+    pub fn find_with_bookmark<D>(
+        &self,
+        query: &FindQuery,
+    ) -> Result<(Vec<D>, Option<String>), Error>
+    where
+        D: DeserializeOwned,
+    {
+        let response = self.find(query)?;
+        Ok((response.docs, response.bookmark))
+    }
+
+    /// Follows the bookmarks until a page comes back short, pages hold `query.limit` documents
+    /// (CouchDB's default of 25 when unset). Collection stops after `max_docs` documents.
+    pub fn find_all<D>(
+        &self,
+        mut query: FindQuery,
+        max_docs: Option<usize>,
+    ) -> Result<Vec<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let page_size = *query.limit.get_or_insert(25);
+        let mut docs = Vec::new();
+        loop {
+            let page: FindResponse<D> = self.find(&query)?;
+            let count = page.docs.len();
+            docs.extend(page.docs);
+
+            if let Some(max_docs) = max_docs {
+                if docs.len() >= max_docs {
+                    docs.truncate(max_docs);
+                    break;
+                }
+            }
+            match page.bookmark {
+                Some(bookmark) if count as u64 >= page_size && count > 0 => {
+                    query.bookmark = Some(bookmark);
+                    query.skip = None;
+                }
+                _ => break,
+            }
+        }
+        Ok(docs)
+    }
+
+    pub fn explain(&self, query: &FindQuery) -> Result<Value, Error> {
+        let res = self.post_json(self.urls().explain()?, query)?;
+        to_result(res)
+    }
+
+    pub fn explain_index_coverage(&self, query: &FindQuery) -> Result<IndexCoverage, Error> {
+        let explain = self.explain(query)?;
+        let index = &explain["index"];
+        let full_scan = index["type"] == "special";
+
+        let mut selector = Vec::new();
+        selector_fields(&explain["selector"], "", &mut selector);
+        let index_fields = index_def_fields(&index["def"]);
+
+        let index_used = match (index["ddoc"].as_str(), index["name"].as_str()) {
+            (Some(ddoc), Some(name)) if !full_scan => Some(format!("{}/{}", ddoc, name)),
+            _ => None,
+        };
+        let estimated_docs_examined = if full_scan {
+            Some(self.database_info()?.doc_count)
+        } else {
+            None
+        };
+
+        Ok(IndexCoverage {
+            index_used,
+            full_scan,
+            estimated_docs_examined,
+            fields_covered_by_index: selector
+                .iter()
+                .filter(|field| !full_scan && index_fields.contains(field))
+                .cloned()
+                .collect(),
+            selector_fields: selector,
+        })
+    }
+
+    /// Parses the `_explain` output. Queries without `fields` are never covering, since they
+    /// return the whole document.
+    pub fn explain_without_executing(&self, query: &FindQuery) -> Result<QueryPlan, Error> {
+        let mut explain = self.explain(query)?;
+        let index = &explain["index"];
+        let index_used = IndexRef {
+            ddoc: index["ddoc"].as_str().unwrap_or_default().to_owned(),
+            name: index["name"].as_str().unwrap_or_default().to_owned(),
+            type_: serde_json::from_value(index["type"].clone())?,
+        };
+        let index_fields = index_def_fields(&index["def"]);
+        let is_indexed = |field: &String| field == "_id" || index_fields.contains(field);
+
+        let mut selector = Vec::new();
+        selector_fields(&explain["selector"], "", &mut selector);
+        let (covered_fields, uncovered_fields) = if index_used.type_ == IndexType::Special {
+            (Vec::new(), selector)
+        } else {
+            selector.into_iter().partition(is_indexed)
+        };
+
+        let covering = match explain["covering"].as_bool() {
+            Some(covering) => covering,
+            None => {
+                index_used.type_ == IndexType::Json
+                    && query
+                        .fields
+                        .as_ref()
+                        .is_some_and(|fields| fields.iter().all(is_indexed))
+            }
+        };
+        let mrargs = explain.get_mut("mrargs").map(Value::take);
+
+        Ok(QueryPlan {
+            estimated_range_scan: index_used.type_ != IndexType::Special && mrargs.is_some(),
+            index_used,
+            covering,
+            selector_coverage: SelectorCoverage {
+                covered_fields,
+                uncovered_fields,
+            },
+            mrargs,
+        })
+    }
+
+    pub fn create_index(&self, def: &IndexDefinition) -> Result<IndexCreateResponse, Error> {
+        let res = self.post_json(self.urls().index()?, def)?;
+        to_result(res)
+    }
+
+    /// CouchDB leaves an identical index alone, so `result` is either `"created"` or `"exists"`.
+    pub fn ensure_index(&self, def: &IndexDefinition) -> Result<IndexCreateResponse, Error> {
+        self.create_index(def)
+    }
+
+    pub fn list_indexes(&self) -> Result<IndexList, Error> {
+        let res = self.get(self.urls().index()?)?;
+        to_result(res)
+    }
+
+    /// `ddoc` may be given with or without the `_design/` prefix.
+    pub fn get_index_info(&self, ddoc: &str, index_name: &str) -> Result<Option<IndexInfo>, Error> {
+        let ddoc = ddoc.trim_start_matches("_design/");
+        Ok(self.list_indexes()?.indexes.into_iter().find(|index| {
+            index.name == index_name
+                && index
+                    .ddoc
+                    .as_deref()
+                    .map(|name| name.trim_start_matches("_design/"))
+                    == Some(ddoc)
+        }))
+    }
+
+    /// `ddoc` may be given with or without the `_design/` prefix.
+    pub fn delete_index(
+        &self,
+        ddoc: &str,
+        index_name: &str,
+        index_type: IndexType,
+    ) -> Result<Value, Error> {
+        let type_ = match index_type {
+            IndexType::Json => "json",
+            IndexType::Text => "text",
+            IndexType::Special => {
+                return Err(Error::Custom(
+                    "special indexes cannot be deleted".to_string(),
+                ))
+            }
+        };
+        let ddoc = ddoc.trim_start_matches("_design/");
+        let res = self.delete(self.urls().db(&["_index", ddoc, type_, index_name])?)?;
+        to_result(res)
+    }
+
+    pub fn delete_index_by_info(&self, info: &IndexInfo) -> Result<Value, Error> {
+        let ddoc = info
+            .ddoc
+            .as_deref()
+            .ok_or(Error::Custom("index has no design document".to_string()))?;
+        self.delete_index(ddoc, &info.name, info.type_)
+    }
+
+    /// `id` is the part after `_local/`.
+    pub fn get_local_doc<D>(&self, id: &str) -> Result<D, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let res = self.get(self.urls().db(&["_local", id])?)?;
+        to_result(res)
+    }
+
+    /// `id` is the part after `_local/`.
+    pub fn put_local_doc<J: Serialize + ?Sized>(
+        &self,
+        id: &str,
+        body: &J,
+    ) -> Result<PutResponse, Error> {
+        let res = self.put_json(self.urls().db(&["_local", id])?, body)?;
+        to_result(res)
+    }
+
+    /// Stores `seq` in `_local/checkpoint_{name}`, overwriting an earlier checkpoint.
+    pub fn save_checkpoint(&self, name: &str, seq: &Value) -> Result<(), Error> {
+        let id = format!("checkpoint_{}", name);
+        let mut doc = serde_json::json!({ "_id": format!("_local/{}", id), "seq": seq });
+        match self.get_local_doc::<Value>(&id) {
+            Ok(existing) => {
+                if let Some(rev) = existing.get("_rev") {
+                    doc["_rev"] = rev.clone();
+                }
+            }
+            Err(e) if e.is_not_found() => (),
+            Err(e) => return Err(e),
+        }
+        self.put_local_doc(&id, &doc)?;
+        Ok(())
+    }
+
+    /// Returns the `seq` saved by [`Client::save_checkpoint`], `None` when there is none yet.
+    pub fn load_checkpoint(&self, name: &str) -> Result<Option<Value>, Error> {
+        match self.get_local_doc::<Value>(&format!("checkpoint_{}", name)) {
+            Ok(mut doc) => Ok(doc.get_mut("seq").map(Value::take)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Runs the migrations that have not been applied yet, in order.
+    ///
+    /// Applied migration names are kept in the `_local/migrations` document, which is
+    /// saved after every migration so a failure does not lose the earlier progress.
+    pub fn apply_migrations(
+        &self,
+        migrations: &[Box<dyn Migration>],
+    ) -> Result<MigrationReport, Error> {
+        let mut state = match self.get_local_doc::<MigrationState>("migrations") {
+            Ok(state) => state,
+            Err(e) if e.is_not_found() => MigrationState::default(),
+            Err(e) => return Err(e),
+        };
+        let mut report = MigrationReport::default();
+
+        for migration in migrations {
+            let name = migration.name().to_owned();
+            if state.applied.contains(&name) {
+                report.skipped.push(name);
+                continue;
+            }
+
+            migration.up(self)?;
+            state.applied.push(name.clone());
+            state.rev = Some(self.put_local_doc("migrations", &state)?.rev);
+            report.applied.push(name);
+        }
+
+        Ok(report)
+    }
+
+    /// Every document whose id starts with `id_prefix`, fetched page by page.
+    ///
+    /// `\u{ffff}` ends the range since it sorts after every character normally found in ids.
+    pub fn get_all_objects_matching<D>(&self, id_prefix: &str) -> Result<Vec<D>, Error>
+    where
+        D: DeserializeOwned,
+    {
+        let query = ViewQuery {
+            startkey: Some(Value::from(id_prefix)),
+            endkey: Some(Value::from(format!("{}\u{ffff}", id_prefix))),
+            include_docs: Some(true),
+            ..ViewQuery::default()
+        };
+
+        let mut objects = Vec::new();
+        self.for_each_all_docs_page::<D, _>(query, Client::ALL_DOCS_PAGE_SIZE, |rows| {
+            objects.extend(rows.into_iter().filter_map(|row| row.doc));
+            Ok(())
+        })?;
+        Ok(objects)
+    }
+
+    /// Copies the documents into the database of `target` in chunks of `chunk_size`.
+    ///
+    /// The revisions are dropped, so the copies start with a fresh history and documents
+    /// that already exist in the target count as failed.
+    ///
+    /// Attachments are copied inline as base64, so a whole chunk including its attachments is
+    /// held in memory. Lower `chunk_size` for databases with large attachments.
+    pub fn copy_database(
+        &self,
+        target: &Client,
+        include_design_docs: bool,
+        chunk_size: usize,
+    ) -> Result<CopyDatabaseReport, Error> {
+        let mut report = CopyDatabaseReport::default();
+        let query = ViewQuery {
+            include_docs: Some(true),
+            attachments: Some(true),
+            ..ViewQuery::default()
+        };
+
+        self.for_each_all_docs_page::<Value, _>(query, chunk_size, |rows| {
+            let docs: Vec<Value> = rows
+                .into_iter()
+                .filter(|row| include_design_docs || !row.id.starts_with("_design/"))
+                .filter_map(|row| row.doc)
+                .map(|mut doc| {
+                    strip_rev(&mut doc);
+                    doc
+                })
+                .collect();
+            if docs.is_empty() {
+                return Ok(());
+            }
+
+            report.total += docs.len() as u64;
+            for result in target.bulk_docs(&docs)? {
+                if result.error.is_none() {
+                    report.success += 1;
+                } else {
+                    report.failed += 1;
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(report)
+    }
+
+    /// Walks `_all_docs` page by page, continuing after the last id of the previous page.
+    fn for_each_all_docs_page<D, F>(
+        &self,
+        mut query: ViewQuery,
+        page_size: usize,
+        mut f: F,
+    ) -> Result<(), Error>
+    where
+        D: DeserializeOwned,
+        F: FnMut(Vec<AllDocsRow<D>>) -> Result<(), Error>,
+    {
+        let page_size = page_size.max(1);
+        query.limit = Some(page_size as u64);
+
+        loop {
+            let rows = self.all_docs::<D>(&query)?.rows;
+            let count = rows.len();
+            if let Some(last) = rows.last() {
+                query.startkey = Some(Value::from(last.id.as_str()));
+                query.skip = Some(1);
+            }
+
+            f(rows)?;
+            if count < page_size {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Queries with `keys` are sent as a POST so long key lists do not end up in the URL.
+    fn view_request(
+        &self,
+        url: reqwest::Url,
+        query: &ViewQuery,
+    ) -> Result<reqwest::blocking::RequestBuilder, Error> {
+        let request = match &query.keys {
+            Some(keys) => {
+                let query = ViewQuery {
+                    keys: None,
+                    ..query.clone()
+                };
+                self.request(Method::POST, url)
+                    .query(&query.to_query_pairs()?)
+                    .json(&serde_json::json!({ "keys": keys }))
+            }
+            None => self
+                .request(Method::GET, url)
+                .query(&query.to_query_pairs()?),
+        };
+        Ok(request)
+    }
+
+    // lower level
+
+    fn request<U: IntoUrl>(&self, method: Method, url: U) -> reqwest::blocking::RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.request_id_header {
+            Some(name) => request.header(name, uuid::Uuid::new_v4().to_string()),
+            None => request,
+        }
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
+        Ok(self.request(Method::GET, url).send()?.json()?)
+    }
+
+    /// Sends extra headers along, a `304 Not Modified` response comes back as `None`.
+    pub fn get_with_headers<U: IntoUrl>(
+        &self,
+        url: U,
+        headers: HeaderMap,
+    ) -> Result<Option<Value>, Error> {
+        let res = self.request(Method::GET, url).headers(headers).send()?;
+        if res.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        Ok(Some(res.json()?))
+    }
+
+    pub fn get_query<U, Q>(&self, url: U, query: &Q) -> Result<Value, Error>
+    where
+        U: IntoUrl,
+        Q: Serialize + ?Sized,
+    {
+        Ok(self.request(Method::GET, url).query(query).send()?.json()?)
+    }
+
+    pub fn head<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
+        let mut map = serde_json::Map::<String, Value>::new();
+
+        for (key, v) in self
+            .request(Method::HEAD, url)
+            .send()?
+            .headers()
+            .into_iter()
+        {
+            if let Ok(value) = v.to_str() {
+                map.insert(key.as_str().to_owned(), value.into());
+            }
+        }
+
+        Ok(Value::Object(map))
+    }
+
+    pub fn put<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
+        Ok(self.request(Method::PUT, url).send()?.json()?)
+    }
+
+    pub fn put_json<U, J>(&self, url: U, json: &J) -> Result<Value, Error>
+    where
+        U: IntoUrl,
+        J: Serialize + ?Sized,
+    {
+        Ok(self.request(Method::PUT, url).json(json).send()?.json()?)
+    }
+
+    pub fn post_json<U, J>(&self, url: U, json: &J) -> Result<Value, Error>
+    where
+        U: IntoUrl,
+        J: Serialize + ?Sized,
+    {
+        Ok(self.request(Method::POST, url).json(json).send()?.json()?)
+    }
+
+    pub fn delete<U: IntoUrl>(&self, url: U) -> Result<Value, Error> {
+        Ok(self.request(Method::DELETE, url).send()?.json()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn selector_combinator_needs_two_selectors() {
+        let one = SelectorBuilder::new().field("type").eq("user");
+        assert!(SelectorBuilder::new().and(vec![one]).build().is_err());
+    }
+
+    #[test]
+    fn selector_operators_nest_under_field() {
+        let selector = SelectorBuilder::new()
+            .field("age")
+            .gt(18)
+            .field("age")
+            .lt(65)
+            .build()
+            .unwrap();
+        assert_eq!(selector, json!({"age": {"$gt": 18, "$lt": 65}}));
+    }
+
+    #[test]
+    fn view_query_sends_either_stale_or_update() {
+        let query = ViewQuery {
+            stale: Some(Stale::Ok),
+            update: Some(Update::Lazy),
+            ..ViewQuery::default()
+        };
+        let v3 = query.to_json().unwrap();
+        assert_eq!(v3.get("update"), Some(&json!("lazy")));
+        assert!(!v3.contains_key("stale"));
+
+        let v2 = ViewQuery {
+            version: CouchDBVersion::V2,
+            ..query
+        }
+        .to_json()
+        .unwrap();
+        assert_eq!(v2.get("stale"), Some(&json!("update_after")));
+        assert!(!v2.contains_key("update"));
+
+        for query in &[
+            ViewQuery {
+                stale: Some(Stale::False),
+                ..ViewQuery::default()
+            },
+            ViewQuery {
+                update: Some(Update::True),
+                ..ViewQuery::default()
+            },
+        ] {
+            let v2 = ViewQuery {
+                version: CouchDBVersion::V2,
+                ..query.clone()
+            }
+            .to_json()
+            .unwrap();
+            assert!(!v2.contains_key("stale"));
+            assert!(!v2.contains_key("update"));
+        }
+    }
+
+    #[test]
+    fn parse_version_drops_pre_release_tag() {
+        assert_eq!(parse_version("3.3.2"), Some((3, 3, 2)));
+        assert_eq!(parse_version("3.3.2-rc.1"), Some((3, 3, 2)));
+    }
+}
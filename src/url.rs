@@ -0,0 +1,177 @@
+use reqwest::Url;
+
+use crate::{Config, Error};
+
+/// Builds the request urls for a [`Config`], every path segment gets percent-encoded.
+pub(crate) struct UrlBuilder<'a> {
+    config: &'a Config,
+}
+
+impl<'a> UrlBuilder<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        UrlBuilder { config }
+    }
+
+    pub fn server(&self, segments: &[&str]) -> Result<Url, Error> {
+        let mut url = Url::parse(&self.config.url)
+            .map_err(|e| Error::Custom(format!("invalid url {}: {}", self.config.url, e)))?;
+
+        if let Some(username) = &self.config.username {
+            url.set_username(username)
+                .map_err(|_| Error::Custom(format!("invalid base url {}", self.config.url)))?;
+        }
+        if let Some(password) = &self.config.password {
+            url.set_password(Some(password))
+                .map_err(|_| Error::Custom(format!("invalid base url {}", self.config.url)))?;
+        }
+
+        url.path_segments_mut()
+            .map_err(|_| Error::Custom(format!("invalid base url {}", self.config.url)))?
+            .pop_if_empty()
+            .extend(segments);
+
+        Ok(url)
+    }
+
+    pub fn db_root(&self) -> Result<Url, Error> {
+        self.db(&[])
+    }
+
+    pub fn db(&self, segments: &[&str]) -> Result<Url, Error> {
+        let mut path = vec![self.config.database_name.as_str()];
+        path.extend_from_slice(segments);
+        self.server(&path)
+    }
+
+    pub fn doc(&self, id: &str) -> Result<Url, Error> {
+        self.db(&doc_segments(id))
+    }
+
+    pub fn doc_rev(&self, id: &str, rev: &str) -> Result<Url, Error> {
+        let mut url = self.doc(id)?;
+        url.query_pairs_mut().append_pair("rev", rev);
+        Ok(url)
+    }
+
+    pub fn attachment(&self, id: &str, name: &str) -> Result<Url, Error> {
+        let mut path = doc_segments(id);
+        path.push(name);
+        self.db(&path)
+    }
+
+    pub fn design(&self, name: &str) -> Result<Url, Error> {
+        self.design_path(name, &[])
+    }
+
+    pub fn design_path(&self, name: &str, segments: &[&str]) -> Result<Url, Error> {
+        let mut path = vec!["_design", name];
+        path.extend_from_slice(segments);
+        self.db(&path)
+    }
+
+    pub fn view(&self, ddoc: &str, view: &str) -> Result<Url, Error> {
+        self.design_path(ddoc, &["_view", view])
+    }
+
+    pub fn all_docs(&self) -> Result<Url, Error> {
+        self.db(&["_all_docs"])
+    }
+
+    pub fn bulk_docs(&self) -> Result<Url, Error> {
+        self.db(&["_bulk_docs"])
+    }
+
+    pub fn bulk_get(&self) -> Result<Url, Error> {
+        self.db(&["_bulk_get"])
+    }
+
+    pub fn changes(&self) -> Result<Url, Error> {
+        self.db(&["_changes"])
+    }
+
+    pub fn find(&self) -> Result<Url, Error> {
+        self.db(&["_find"])
+    }
+
+    pub fn explain(&self) -> Result<Url, Error> {
+        self.db(&["_explain"])
+    }
+
+    pub fn index(&self) -> Result<Url, Error> {
+        self.db(&["_index"])
+    }
+}
+
+/// Design and local documents keep their prefix as a separate path segment,
+/// so the `/` in `_design/name` is not encoded.
+fn doc_segments(id: &str) -> Vec<&str> {
+    for prefix in &["_design", "_local"] {
+        if let Some(name) = id
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix('/'))
+        {
+            return vec![prefix, name];
+        }
+    }
+    vec![id]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(url: &str, database_name: &str) -> Config {
+        Config {
+            url: url.to_string(),
+            database_name: database_name.to_string(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn doc_id_slash_is_encoded() {
+        let config = config("http://localhost:5984", "db");
+        let url = UrlBuilder::new(&config).doc("a/b").unwrap();
+        assert_eq!(url.as_str(), "http://localhost:5984/db/a%2Fb");
+    }
+
+    #[test]
+    fn design_and_local_prefix_keep_slash() {
+        let config = config("http://localhost:5984", "db");
+        let urls = UrlBuilder::new(&config);
+        assert_eq!(
+            urls.doc("_design/name").unwrap().as_str(),
+            "http://localhost:5984/db/_design/name"
+        );
+        assert_eq!(
+            urls.doc("_local/name").unwrap().as_str(),
+            "http://localhost:5984/db/_local/name"
+        );
+        assert_eq!(
+            urls.doc("_design/a/b").unwrap().as_str(),
+            "http://localhost:5984/db/_design/a%2Fb"
+        );
+    }
+
+    #[test]
+    fn database_name_slash_is_encoded() {
+        let config = config("http://localhost:5984", "team/db");
+        let url = UrlBuilder::new(&config).doc("doc").unwrap();
+        assert_eq!(url.as_str(), "http://localhost:5984/team%2Fdb/doc");
+    }
+
+    #[test]
+    fn base_url_trailing_slash() {
+        let config = config("http://localhost:5984/", "db");
+        let url = UrlBuilder::new(&config).doc("doc").unwrap();
+        assert_eq!(url.as_str(), "http://localhost:5984/db/doc");
+    }
+
+    #[test]
+    fn doc_rev_query_is_encoded() {
+        let config = config("http://localhost:5984", "db");
+        let url = UrlBuilder::new(&config).doc_rev("doc", "1-a&b=c").unwrap();
+        assert_eq!(url.as_str(), "http://localhost:5984/db/doc?rev=1-a%26b%3Dc");
+        assert_eq!(url.query_pairs().next().unwrap().1, "1-a&b=c");
+    }
+}